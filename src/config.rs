@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{Value, Variables};
+
+/// Variables read from a key-value configuration file.
+///
+/// Keys in sections or nested tables are joined with `.`, e.g. `host` in the section `[api]` is
+/// the variable `api.host`. Arrays are lists, and JSON nulls are undefined.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigVariables {
+    variables: BTreeMap<String, Value>,
+}
+
+/// An error reading a configuration file.
+///
+/// The variants depend on the enabled features, so the enum is non-exhaustive to keep the
+/// features additive.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// An INI line that is not blank, a comment, a `[section]` header or a `key = value` pair.
+    InvalidIniLine(usize),
+    /// A JSON document that is not an object.
+    #[cfg(feature = "json")]
+    NotAnObject,
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    #[cfg(feature = "toml")]
+    Toml(toml::de::Error),
+}
+
+impl ConfigVariables {
+    /// Parses an INI file. Comments start with `;` or `#`, and values may be quoted with `"`.
+    pub fn from_ini(s: &str) -> Result<Self, ConfigError> {
+        let mut variables = BTreeMap::new();
+        let mut section = String::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with([';', '#']) {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (k, v) = line
+                .split_once('=')
+                .ok_or(ConfigError::InvalidIniLine(i + 1))?;
+            let (k, v) = (k.trim(), v.trim());
+            if k.is_empty() {
+                return Err(ConfigError::InvalidIniLine(i + 1));
+            }
+            let v = v
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(v);
+            variables.insert(join_key(&section, k), Value::from_string(v));
+        }
+        Ok(ConfigVariables { variables })
+    }
+
+    #[cfg(feature = "json")]
+    pub fn from_json(s: &str) -> Result<Self, ConfigError> {
+        let object = match serde_json::from_str(s).map_err(ConfigError::Json)? {
+            serde_json::Value::Object(object) => object,
+            _ => return Err(ConfigError::NotAnObject),
+        };
+        let mut variables = BTreeMap::new();
+        insert_json_object(&mut variables, "", object);
+        Ok(ConfigVariables { variables })
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        let table = s.parse().map_err(ConfigError::Toml)?;
+        let mut variables = BTreeMap::new();
+        insert_toml_table(&mut variables, "", table);
+        Ok(ConfigVariables { variables })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.variables.iter().map(|(k, v)| (k.as_str(), v))
+    }
+}
+
+impl<'a> Variables<'a, &'a Value> for ConfigVariables {
+    fn get(&'a self, k: &'a str) -> Option<&'a Value> {
+        self.variables.get(k)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::InvalidIniLine(line) => write!(f, "invalid INI at line {}", line),
+            #[cfg(feature = "json")]
+            ConfigError::NotAnObject => f.write_str("configuration must be an object"),
+            #[cfg(feature = "json")]
+            ConfigError::Json(e) => e.fmt(f),
+            #[cfg(feature = "toml")]
+            ConfigError::Toml(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "json")]
+            ConfigError::Json(e) => Some(e),
+            #[cfg(feature = "toml")]
+            ConfigError::Toml(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+fn join_key(prefix: &str, k: &str) -> String {
+    if prefix.is_empty() {
+        k.to_string()
+    } else {
+        format!("{}.{}", prefix, k)
+    }
+}
+
+#[cfg(feature = "json")]
+fn insert_json_object(
+    variables: &mut BTreeMap<String, Value>,
+    prefix: &str,
+    object: serde_json::Map<String, serde_json::Value>,
+) {
+    for (k, v) in object {
+        let k = join_key(prefix, &k);
+        match v {
+            serde_json::Value::Object(object) => insert_json_object(variables, &k, object),
+            v => {
+                variables.insert(k, json_value(v));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_value(v: serde_json::Value) -> Value {
+    match v {
+        serde_json::Value::Null => Value::Undefined,
+        serde_json::Value::Bool(v) => Value::Boolean(v),
+        serde_json::Value::Number(v) => match (v.as_i64(), v.as_f64()) {
+            (Some(v), _) => Value::Integer(v),
+            (None, Some(f)) if v.is_f64() => Value::Float(f),
+            _ => Value::String(v.to_string()),
+        },
+        serde_json::Value::String(v) => Value::String(v),
+        serde_json::Value::Array(v) => Value::List(v.into_iter().map(json_value).collect()),
+        serde_json::Value::Object(v) => {
+            Value::AssociativeArray(v.into_iter().map(|(k, v)| (k, json_value(v))).collect())
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+fn insert_toml_table(variables: &mut BTreeMap<String, Value>, prefix: &str, table: toml::Table) {
+    for (k, v) in table {
+        let k = join_key(prefix, &k);
+        match v {
+            toml::Value::Table(table) => insert_toml_table(variables, &k, table),
+            v => {
+                variables.insert(k, toml_value(v));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+fn toml_value(v: toml::Value) -> Value {
+    match v {
+        toml::Value::String(v) => Value::String(v),
+        toml::Value::Integer(v) => Value::Integer(v),
+        toml::Value::Float(v) => Value::Float(v),
+        toml::Value::Boolean(v) => Value::Boolean(v),
+        toml::Value::Datetime(v) => Value::String(v.to_string()),
+        toml::Value::Array(v) => Value::List(v.into_iter().map(toml_value).collect()),
+        toml::Value::Table(v) => {
+            Value::AssociativeArray(v.into_iter().map(|(k, v)| (k, toml_value(v))).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UriTemplate;
+
+    #[test]
+    fn test_from_ini() {
+        let ini = "; endpoints\nversion = 2\n\n[api]\nhost = \"example.com\"\n# path\npath=a/b\n";
+        let variables = ConfigVariables::from_ini(ini).unwrap();
+        let left: Vec<_> = variables.iter().map(|(k, v)| (k, v.clone())).collect();
+        let right = vec![
+            ("api.host", Value::from_string("example.com")),
+            ("api.path", Value::from_string("a/b")),
+            ("version", Value::from_string("2")),
+        ];
+        assert_eq!(left, right);
+
+        let uri_template = UriTemplate::parse("https://{api.host}/v{version}/{+api.path}");
+        assert_eq!(
+            uri_template.expand(&variables),
+            "https://example.com/v2/a/b"
+        );
+
+        let left = ConfigVariables::from_ini("[api]\nhost\n").unwrap_err();
+        assert_eq!(left.to_string(), "invalid INI at line 2");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_json() {
+        let json = r#"{"api":{"host":"example.com","port":8080},"ids":[1,2],"q":null}"#;
+        let variables = ConfigVariables::from_json(json).unwrap();
+        let uri_template = UriTemplate::parse("https://{api.host}:{api.port}{?ids,q}");
+        assert_eq!(
+            uri_template.expand(&variables),
+            "https://example.com:8080?ids=1,2"
+        );
+
+        let left = ConfigVariables::from_json("[]").unwrap_err();
+        assert_eq!(left.to_string(), "configuration must be an object");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_from_toml() {
+        let toml = "ids = [1, 2]\n\n[api]\nhost = \"example.com\"\nport = 8080\n";
+        let variables = ConfigVariables::from_toml(toml).unwrap();
+        let uri_template = UriTemplate::parse("https://{api.host}:{api.port}{?ids}");
+        assert_eq!(
+            uri_template.expand(&variables),
+            "https://example.com:8080?ids=1,2"
+        );
+
+        assert!(ConfigVariables::from_toml("api =").is_err());
+    }
+}
@@ -1,22 +1,35 @@
-pub type PushAllow = fn(&mut String, &str);
+use std::fmt::{self, Write};
+use std::ops::Range;
 
-pub fn push_literal(dst: &mut String, src: &str) {
-    pct_encode(is_unreserved, dst, src);
+pub type PushAllow = fn(&mut dyn Write, &str) -> fmt::Result;
+
+pub fn push_literal(dst: &mut dyn Write, src: &str) -> fmt::Result {
+    pct_encode(is_unreserved, dst, src)
+}
+
+/// Pushes literal text, pct-encoding the characters that are neither unreserved nor reserved and
+/// keeping pct-encoded triplets, see RFC 6570 section 3.1.
+pub fn push_literal_encoded(dst: &mut dyn Write, src: &str) -> fmt::Result {
+    pct_encode(is_unreserved_reserved, dst, src)
 }
 
-pub fn push_allow_unreserved(dst: &mut String, src: &str) {
-    src.chars().for_each(|c| push_char(is_unreserved, dst, c));
+pub fn push_allow_unreserved(dst: &mut dyn Write, src: &str) -> fmt::Result {
+    src.chars()
+        .try_for_each(|c| push_char(is_unreserved, dst, c))
 }
 
-pub fn push_allow_unreserved_reserved(dst: &mut String, src: &str) {
+pub fn push_allow_unreserved_reserved(dst: &mut dyn Write, src: &str) -> fmt::Result {
     src.chars()
-        .for_each(|c| push_char(is_unreserved_reserved, dst, c));
+        .try_for_each(|c| push_char(is_unreserved_reserved, dst, c))
 }
 
+// The ranges are spelled as in the RFC 5234 ABNF core rules.
+#[allow(clippy::manual_is_ascii_check)]
 pub fn is_alpha(c: char) -> bool {
     matches!(c, '\x41'..='\x5A' | '\x61'..='\x7A')
 }
 
+#[allow(clippy::manual_is_ascii_check)]
 pub fn is_digit(c: char) -> bool {
     matches!(c, '\x30'..='\x39')
 }
@@ -50,7 +63,52 @@ pub fn is_unreserved_reserved(c: char) -> bool {
     is_reserved(c) || is_unreserved(c)
 }
 
-type IsAllowed = fn(char) -> bool;
+/// Whether `c` may appear in a template literal, see RFC 6570 section 2.1. A `%` must also start
+/// a pct-encoded triplet, see [`invalid_literal_spans`].
+pub fn is_literal_char(c: char) -> bool {
+    match c {
+        '!' | '#' | '$' | '&' | '('..=';' | '=' | '?'..='[' | ']' | '_' | 'a'..='z' | '~' => true,
+        c => is_ucschar(c) || is_iprivate(c),
+    }
+}
+
+fn is_ucschar(c: char) -> bool {
+    // Planes 1 to 14 exclude the last two code points of each plane, and plane 14 starts at
+    // U+E1000.
+    let c = u32::from(c);
+    match c {
+        0xA0..=0xD7FF | 0xF900..=0xFDCF | 0xFDF0..=0xFFEF => true,
+        0x10000..=0xEFFFD => c & 0xFFFF <= 0xFFFD && !(0xE0000..0xE1000).contains(&c),
+        _ => false,
+    }
+}
+
+fn is_iprivate(c: char) -> bool {
+    matches!(u32::from(c), 0xE000..=0xF8FF | 0xF0000..=0xFFFFD | 0x100000..=0x10FFFD)
+}
+
+/// The byte ranges of the characters in `literal` that are not valid template literal
+/// characters, including a `%` that does not start a pct-encoded triplet.
+pub fn invalid_literal_spans(literal: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut chars = literal.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '%' {
+            let mut rest = literal[i + 1..].chars();
+            if rest.next().is_some_and(is_hexdig) && rest.next().is_some_and(is_hexdig) {
+                chars.next();
+                chars.next();
+                continue;
+            }
+        } else if is_literal_char(c) {
+            continue;
+        }
+        spans.push(i..i + c.len_utf8());
+    }
+    spans
+}
+
+pub type IsAllowed = fn(char) -> bool;
 
 enum PctEncodeState {
     S0,
@@ -59,71 +117,93 @@ enum PctEncodeState {
 }
 
 impl PctEncodeState {
-    fn transition(self, is_allowed: IsAllowed, dst: &mut String, c: char) -> Self {
+    fn transition(
+        self,
+        is_allowed: IsAllowed,
+        dst: &mut dyn Write,
+        c: char,
+    ) -> Result<Self, fmt::Error> {
         match self {
-            PctEncodeState::S1 if is_hexdig(c) => PctEncodeState::S2(c),
+            PctEncodeState::S1 if is_hexdig(c) => Ok(PctEncodeState::S2(c)),
             PctEncodeState::S2(b) if is_hexdig(c) => {
-                dst.push('%');
-                dst.push(b);
-                dst.push(c);
-                PctEncodeState::S0
+                dst.write_char('%')?;
+                dst.write_char(b)?;
+                dst.write_char(c)?;
+                Ok(PctEncodeState::S0)
             }
             _ => {
-                self.push_incomplete(is_allowed, dst);
+                self.push_incomplete(is_allowed, dst)?;
                 if '%' == c {
-                    PctEncodeState::S1
+                    Ok(PctEncodeState::S1)
                 } else {
-                    push_char(is_allowed, dst, c);
-                    PctEncodeState::S0
+                    push_char(is_allowed, dst, c)?;
+                    Ok(PctEncodeState::S0)
                 }
             }
         }
     }
 
-    fn push_incomplete(&self, is_allowed: IsAllowed, dst: &mut String) {
+    fn push_incomplete(&self, is_allowed: IsAllowed, dst: &mut dyn Write) -> fmt::Result {
         match self {
-            PctEncodeState::S0 => {}
-            PctEncodeState::S1 => {
-                dst.push_str("%25");
-            }
+            PctEncodeState::S0 => Ok(()),
+            PctEncodeState::S1 => dst.write_str("%25"),
             PctEncodeState::S2(c) => {
-                dst.push_str("%25");
-                push_char(is_allowed, dst, *c);
+                dst.write_str("%25")?;
+                push_char(is_allowed, dst, *c)
             }
         }
     }
 }
 
-fn pct_encode(is_allowed: IsAllowed, dst: &mut String, src: &str) {
+fn pct_encode(is_allowed: IsAllowed, dst: &mut dyn Write, src: &str) -> fmt::Result {
     let mut state = PctEncodeState::S0;
     for c in src.chars() {
-        state = state.transition(is_allowed, dst, c);
+        state = state.transition(is_allowed, dst, c)?;
     }
-    state.push_incomplete(is_allowed, dst);
+    state.push_incomplete(is_allowed, dst)
 }
 
-fn push_char(is_allowed: IsAllowed, dst: &mut String, c: char) {
+fn push_char(is_allowed: IsAllowed, dst: &mut dyn Write, c: char) -> fmt::Result {
     if is_allowed(c) {
-        dst.push(c);
+        dst.write_char(c)
     } else {
-        push_hex_char(dst, c);
+        push_hex_char(dst, c)
     }
 }
 
-fn push_hex_char(dst: &mut String, c: char) {
+fn push_hex_char(dst: &mut dyn Write, c: char) -> fmt::Result {
     let mut buf = [0; 4];
     let s = c.encode_utf8(&mut buf);
-    s.as_bytes().iter().for_each(|b| push_hex_u8(dst, *b));
+    s.as_bytes().iter().try_for_each(|b| push_hex_u8(dst, *b))
+}
+
+pub fn pct_decode(src: &str) -> Option<String> {
+    let mut dst = Vec::with_capacity(src.len());
+    let mut bytes = src.bytes();
+    while let Some(b) = bytes.next() {
+        if b'%' == b {
+            let hi = bytes.next().and_then(hex_value)?;
+            let lo = bytes.next().and_then(hex_value)?;
+            dst.push(hi << 4 | lo);
+        } else {
+            dst.push(b);
+        }
+    }
+    String::from_utf8(dst).ok()
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    char::from(b).to_digit(16).map(|d| d as u8)
 }
 
 const HEX_DIGITS: &[u8] = b"0123456789ABCDEF";
 
-fn push_hex_u8(dst: &mut String, b: u8) {
+fn push_hex_u8(dst: &mut dyn Write, b: u8) -> fmt::Result {
     let hi = char::from(HEX_DIGITS[usize::from(b >> 4)]);
     let lo = char::from(HEX_DIGITS[usize::from(b & 0xF)]);
-    dst.push('%');
-    dst.push(hi);
-    dst.push(lo);
+    dst.write_char('%')?;
+    dst.write_char(hi)?;
+    dst.write_char(lo)
 }
 
 #[cfg(test)]
@@ -133,11 +213,28 @@ mod tests {
     macro_rules! test {
         ($s:expr, $right:expr) => {
             let mut left = String::new();
-            pct_encode(|_| true, &mut left, $s);
+            pct_encode(|_| true, &mut left, $s).unwrap();
             assert_eq!(left, $right);
         };
     }
 
+    #[test]
+    fn test_pct_decode() {
+        assert_eq!(
+            pct_decode("Hello%20World!").as_deref(),
+            Some("Hello World!")
+        );
+        assert_eq!(
+            pct_decode("%C3%A9t%c3%a9").as_deref(),
+            Some("\u{e9}t\u{e9}")
+        );
+        assert_eq!(pct_decode(""), Some(String::new()));
+        assert_eq!(pct_decode("%"), None);
+        assert_eq!(pct_decode("%2"), None);
+        assert_eq!(pct_decode("%2x"), None);
+        assert_eq!(pct_decode("%FF"), None);
+    }
+
     #[test]
     fn test_pct_encode() {
         test!("Hello%20World!", "Hello%20World!");
@@ -0,0 +1,135 @@
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Value, Variables};
+
+/// Variables read from the process environment.
+///
+/// A variable name is mapped to an environment variable name, by default by replacing `.` with
+/// `_` and converting to upper case, and then prefixed, e.g. `api.host` is read from
+/// `APP_API_HOST` with the prefix `APP_`. Environment variables that are not set or not valid
+/// Unicode are undefined.
+#[derive(Clone)]
+pub struct EnvVariables {
+    prefix: String,
+    map_name: Arc<MapName>,
+    lookup: Arc<Lookup>,
+}
+
+type MapName = dyn Fn(&str) -> String + Send + Sync;
+
+type Lookup = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+impl EnvVariables {
+    pub fn new() -> Self {
+        EnvVariables {
+            prefix: String::new(),
+            map_name: Arc::new(default_map_name),
+            lookup: Arc::new(default_lookup),
+        }
+    }
+
+    pub fn prefix<S>(mut self, prefix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Replaces how variable names are mapped to environment variable names, before the prefix
+    /// is added.
+    pub fn map_name<F>(mut self, map_name: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.map_name = Arc::new(map_name);
+        self
+    }
+
+    /// Replaces how environment variables are read, e.g. to read them from a snapshot taken at
+    /// startup instead of the process environment.
+    pub fn lookup<F>(mut self, lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.lookup = Arc::new(lookup);
+        self
+    }
+
+    /// The name of the environment variable that `varname` is read from.
+    pub fn env_name(&self, varname: &str) -> String {
+        let mut name = self.prefix.clone();
+        name.push_str(&(self.map_name)(varname));
+        name
+    }
+}
+
+impl Default for EnvVariables {
+    fn default() -> Self {
+        EnvVariables::new()
+    }
+}
+
+impl fmt::Debug for EnvVariables {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EnvVariables")
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> Variables<'a, Value> for EnvVariables {
+    fn get(&'a self, k: &'a str) -> Option<Value> {
+        (self.lookup)(&self.env_name(k)).map(Value::String)
+    }
+}
+
+fn default_map_name(varname: &str) -> String {
+    varname.replace('.', "_").to_uppercase()
+}
+
+fn default_lookup(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::UriTemplate;
+
+    #[test]
+    fn test_env_variables() {
+        let snapshot: HashMap<_, _> = vec![(
+            "URI_TEMPLATE_TEST_API_HOST".to_string(),
+            "example.com".to_string(),
+        )]
+        .into_iter()
+        .collect();
+        let variables = EnvVariables::new()
+            .prefix("URI_TEMPLATE_TEST_")
+            .lookup(move |name| snapshot.get(name).cloned());
+        assert_eq!(variables.env_name("api.host"), "URI_TEMPLATE_TEST_API_HOST");
+
+        let uri_template = UriTemplate::parse("https://{api.host}{/api.version}");
+        assert_eq!(uri_template.expand(&variables), "https://example.com");
+
+        let variables = variables.map_name(|k| k.replace('.', "__").to_lowercase());
+        assert_eq!(
+            variables.env_name("api.host"),
+            "URI_TEMPLATE_TEST_api__host"
+        );
+        assert_eq!(uri_template.expand(&variables), "https://");
+    }
+
+    #[test]
+    fn test_env_variables_process() {
+        let variables = EnvVariables::new().map_name(str::to_string);
+        let uri_template = UriTemplate::parse("{PATH}");
+        let right = vec![("PATH", env::var("PATH").unwrap_or_default())];
+        assert_eq!(uri_template.expand(&variables), uri_template.expand(&right));
+    }
+}
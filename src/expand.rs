@@ -1,57 +1,208 @@
-use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::io;
 
 use crate::encoding::{
-    push_allow_unreserved, push_allow_unreserved_reserved, push_literal, PushAllow,
+    is_unreserved, is_unreserved_reserved, push_allow_unreserved, push_allow_unreserved_reserved,
+    push_literal, IsAllowed, PushAllow,
 };
 use crate::item::{Expression, Item, ModifierLevel4, Operator, Varspec};
-use crate::{Value, Variables};
+use crate::position::{variable_usages, Position};
+use crate::value_ref::{is_defined, with_scalar, AsValueRef, ValueRef};
+use crate::Variables;
 
-pub fn expand_items<'a, V, B>(items: &'a [Item], variables: &'a V) -> String
+/// Which variables [`UriTemplate::try_expand`](crate::UriTemplate::try_expand) requires to be
+/// defined.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExpandPolicy {
+    /// Undefined variables are skipped, like [`UriTemplate::expand`](crate::UriTemplate::expand).
+    Lenient,
+    /// Variables in the path, before any query or fragment, must be defined.
+    RequirePath,
+    RequireAll,
+}
+
+/// The variables that an [`ExpandPolicy`] requires but that are undefined.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpandError {
+    missing: Vec<String>,
+}
+
+impl ExpandError {
+    /// The names of the missing variables, in order of first appearance.
+    pub fn missing(&self) -> &[String] {
+        &self.missing
+    }
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.missing.len() == 1 {
+            f.write_str("undefined variable ")?;
+        } else {
+            f.write_str("undefined variables ")?;
+        }
+        for (i, varname) in self.missing.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{}`", varname)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ExpandError {}
+
+/// Checks that every variable required by `policy` is defined. Empty lists and associative
+/// arrays count as undefined, as they do in the expansion, but an explicit [`Value::Undefined`]
+/// counts as set.
+pub fn check_defined<'a, V, B>(
+    items: &'a [Item],
+    variables: &'a V,
+    policy: ExpandPolicy,
+) -> Result<(), ExpandError>
+where
+    V: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
+{
+    let mut missing: Vec<String> = Vec::new();
+    for usage in variable_usages(items) {
+        let required = match policy {
+            ExpandPolicy::Lenient => false,
+            ExpandPolicy::RequirePath => usage.position() == Position::Path,
+            ExpandPolicy::RequireAll => true,
+        };
+        let varname = usage.name();
+        if required
+            && !missing.iter().any(|k| k == varname)
+            && !variables.get(varname).is_some_and(|value| {
+                let value = value.as_value_ref();
+                matches!(value, ValueRef::Undefined) || is_defined(value)
+            })
+        {
+            missing.push(varname.to_string());
+        }
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(ExpandError { missing })
+    }
+}
+
+pub fn expand_items<'a, V, B>(
+    items: &'a [Item],
+    variables: &'a V,
+    dst: &mut dyn Write,
+) -> fmt::Result
 where
-    V: Variables<'a, B>,
-    B: Borrow<Value>,
+    V: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
 {
-    let mut dst = String::new();
     for item in items {
         match item {
-            Item::Literal(literal) => expand_literal(&mut dst, literal),
-            Item::Expression(expression) => expand_expression(variables, &mut dst, expression),
+            Item::Literal(literal) => expand_literal(dst, literal)?,
+            Item::Expression(expression) => expand_expression(variables, dst, expression)?,
         }
     }
-    dst
+    Ok(())
+}
+
+pub fn expand_items_io<'a, V, B>(
+    items: &'a [Item],
+    variables: &'a V,
+    dst: &mut dyn io::Write,
+) -> io::Result<()>
+where
+    V: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
+{
+    let mut writer = IoWriter {
+        inner: dst,
+        result: Ok(()),
+    };
+    match expand_items(items, variables, &mut writer) {
+        Ok(()) => Ok(()),
+        Err(fmt::Error) => writer.result,
+    }
+}
+
+/// Writes to an `io::Write`, keeping the I/O error that `fmt::Error` cannot carry.
+struct IoWriter<'a> {
+    inner: &'a mut dyn io::Write,
+    result: io::Result<()>,
+}
+
+impl Write for IoWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.result = Err(e);
+            fmt::Error
+        })
+    }
 }
 
-fn expand_literal(dst: &mut String, literal: &str) {
-    dst.push_str(literal);
+fn expand_literal(dst: &mut dyn Write, literal: &str) -> fmt::Result {
+    dst.write_str(literal)
+}
+
+fn expand_expression<'a, V, B>(
+    variables: &'a V,
+    dst: &mut dyn Write,
+    expression: &'a Expression,
+) -> fmt::Result
+where
+    V: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
+{
+    expand_varspecs(
+        variables,
+        dst,
+        expression.operator,
+        &expression.variable_list,
+    )?;
+    Ok(())
 }
 
-fn expand_expression<'a, V, B>(variables: &'a V, dst: &mut String, expression: &'a Expression)
+/// Expands `varspecs` as an expression with `operator`, returning whether any of them was
+/// defined, i.e. whether the expression's `first` prefix was written.
+pub fn expand_varspecs<'a, V, B>(
+    variables: &'a V,
+    dst: &mut dyn Write,
+    operator: Option<Operator>,
+    varspecs: &'a [Varspec],
+) -> Result<bool, fmt::Error>
 where
-    V: Variables<'a, B>,
-    B: Borrow<Value>,
+    V: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
 {
-    let operator_table = get_operator_table(expression.operator);
+    let operator_table = get_operator_table(operator);
     let mut push_sep = make_push_sep(operator_table.first, operator_table.sep);
-    for varspec in &expression.variable_list {
+    let mut defined = false;
+    for varspec in varspecs {
         if let Some(value) = variables.get(&varspec.varname) {
-            if let Some(ModifierLevel4::Explode) = varspec.modifier_level4 {
-                explode_varspec(dst, &operator_table, &mut push_sep, varspec, value.borrow());
+            let value = value.as_value_ref();
+            defined |= if let Some(ModifierLevel4::Explode) = varspec.modifier_level4 {
+                explode_varspec(dst, &operator_table, &mut push_sep, varspec, value)?
             } else {
-                expand_varspec(dst, &operator_table, &mut push_sep, varspec, value.borrow());
-            }
+                expand_varspec(dst, &operator_table, &mut push_sep, varspec, value)?
+            };
         }
     }
+    Ok(defined)
 }
 
-struct OperatorTable {
-    first: &'static str,
-    sep: &'static str,
-    named: bool,
-    ifemp: &'static str,
-    allow: PushAllow,
+pub struct OperatorTable {
+    pub first: &'static str,
+    pub sep: &'static str,
+    pub named: bool,
+    pub ifemp: &'static str,
+    pub allow: PushAllow,
+    pub is_allowed: IsAllowed,
 }
 
-fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
+pub fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
     match operator {
         None => OperatorTable {
             first: "",
@@ -59,6 +210,7 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: false,
             ifemp: "",
             allow: push_allow_unreserved,
+            is_allowed: is_unreserved,
         },
         Some(Operator::Reserved) => OperatorTable {
             first: "",
@@ -66,6 +218,7 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: false,
             ifemp: "",
             allow: push_allow_unreserved_reserved,
+            is_allowed: is_unreserved_reserved,
         },
         Some(Operator::Fragment) => OperatorTable {
             first: "#",
@@ -73,6 +226,7 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: false,
             ifemp: "",
             allow: push_allow_unreserved_reserved,
+            is_allowed: is_unreserved_reserved,
         },
         Some(Operator::Label) => OperatorTable {
             first: ".",
@@ -80,6 +234,7 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: false,
             ifemp: "",
             allow: push_allow_unreserved,
+            is_allowed: is_unreserved,
         },
         Some(Operator::PathSegment) => OperatorTable {
             first: "/",
@@ -87,6 +242,7 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: false,
             ifemp: "",
             allow: push_allow_unreserved,
+            is_allowed: is_unreserved,
         },
         Some(Operator::PathParameter) => OperatorTable {
             first: ";",
@@ -94,6 +250,7 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: true,
             ifemp: "",
             allow: push_allow_unreserved,
+            is_allowed: is_unreserved,
         },
         Some(Operator::FormQuery) => OperatorTable {
             first: "?",
@@ -101,6 +258,7 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: true,
             ifemp: "=",
             allow: push_allow_unreserved,
+            is_allowed: is_unreserved,
         },
         Some(Operator::FormContinuation) => OperatorTable {
             first: "&",
@@ -108,215 +266,176 @@ fn get_operator_table(operator: Option<Operator>) -> OperatorTable {
             named: true,
             ifemp: "=",
             allow: push_allow_unreserved,
+            is_allowed: is_unreserved,
         },
     }
 }
 
-fn make_push_sep(first: &'static str, sep: &'static str) -> impl FnMut(&mut String) {
+fn make_push_sep(
+    first: &'static str,
+    sep: &'static str,
+) -> impl FnMut(&mut dyn Write) -> fmt::Result {
     let mut s = first;
-    move |dst: &mut String| {
-        dst.push_str(s);
+    move |dst: &mut dyn Write| {
+        dst.write_str(s)?;
         s = sep;
+        Ok(())
     }
 }
 
 fn explode_varspec<F>(
-    dst: &mut String,
+    dst: &mut dyn Write,
     operator_table: &OperatorTable,
     push_sep: &mut F,
     varspec: &Varspec,
-    value: &Value,
-) where
-    F: FnMut(&mut String),
+    value: ValueRef<'_>,
+) -> Result<bool, fmt::Error>
+where
+    F: FnMut(&mut dyn Write) -> fmt::Result,
 {
-    match value {
-        Value::AssociativeArray(value) => {
-            if !value.is_empty() {
-                push_sep(dst);
-                explode_varspec_assoc(dst, operator_table, value);
-            }
-        }
-        Value::List(value) => {
-            if !value.is_empty() {
-                push_sep(dst);
-                explode_varspec_list(dst, operator_table, varspec, value);
-            }
-        }
-        Value::String(value) => {
-            push_sep(dst);
-            explode_varspec_string(dst, operator_table, varspec, value);
-        }
-    }
-}
-
-fn explode_varspec_assoc(
-    dst: &mut String,
-    operator_table: &OperatorTable,
-    value: &[(String, String)],
-) {
-    let mut push_sep = make_push_sep("", operator_table.sep);
-    if !operator_table.named {
-        expand_assoc(dst, &operator_table.allow, &mut push_sep, "=", value);
-    } else {
-        for (k, v) in value {
-            push_sep(dst);
-            push_allow_unreserved(dst, k);
-            if v.is_empty() {
-                dst.push_str(operator_table.ifemp);
-            } else {
-                dst.push('=');
-                (operator_table.allow)(dst, v);
-            }
+    let mut defined = false;
+    let mut push_item_sep = |dst: &mut dyn Write| {
+        if defined {
+            dst.write_str(operator_table.sep)
+        } else {
+            defined = true;
+            push_sep(dst)
         }
-    }
-}
-
-fn explode_varspec_list(
-    dst: &mut String,
-    operator_table: &OperatorTable,
-    varspec: &Varspec,
-    value: &[String],
-) {
-    let mut push_sep = make_push_sep("", operator_table.sep);
-    if !operator_table.named {
-        expand_list(dst, &operator_table.allow, &mut push_sep, value);
-    } else {
-        for v in value {
-            push_sep(dst);
-            push_literal(dst, &varspec.varname);
-            if v.is_empty() {
-                dst.push_str(operator_table.ifemp);
+    };
+    match value {
+        ValueRef::AssociativeArray(value) => value.for_each_entry(&mut |k, v| {
+            with_scalar(v, |v| {
+                push_item_sep(dst)?;
+                if !operator_table.named {
+                    (operator_table.allow)(dst, k)?;
+                    dst.write_char('=')?;
+                    (operator_table.allow)(dst, v)
+                } else {
+                    push_allow_unreserved(dst, k)?;
+                    push_value(dst, operator_table, v)
+                }
+            })
+            .unwrap_or(Ok(()))
+        })?,
+        ValueRef::List(value) => value.for_each_item(&mut |v| {
+            with_scalar(v, |v| {
+                push_item_sep(dst)?;
+                if !operator_table.named {
+                    (operator_table.allow)(dst, v)
+                } else {
+                    push_literal(dst, &varspec.varname)?;
+                    push_value(dst, operator_table, v)
+                }
+            })
+            .unwrap_or(Ok(()))
+        })?,
+        value => with_scalar(value, |v| {
+            push_item_sep(dst)?;
+            if !operator_table.named {
+                (operator_table.allow)(dst, v)
             } else {
-                dst.push('=');
-                (operator_table.allow)(dst, v);
+                push_literal(dst, &varspec.varname)?;
+                push_value(dst, operator_table, v)
             }
-        }
-    }
-}
-
-fn explode_varspec_string(
-    dst: &mut String,
-    operator_table: &OperatorTable,
-    varspec: &Varspec,
-    value: &str,
-) {
-    if !operator_table.named {
-        (operator_table.allow)(dst, value);
-    } else {
-        push_literal(dst, &varspec.varname);
-        if value.is_empty() {
-            dst.push_str(operator_table.ifemp);
-        } else {
-            dst.push('=');
-            (operator_table.allow)(dst, value);
-        }
+        })
+        .unwrap_or(Ok(()))?,
     }
+    Ok(defined)
 }
 
 fn expand_varspec<F>(
-    dst: &mut String,
+    dst: &mut dyn Write,
     operator_table: &OperatorTable,
     push_sep: &mut F,
     varspec: &Varspec,
-    value: &Value,
-) where
-    F: FnMut(&mut String),
+    value: ValueRef<'_>,
+) -> Result<bool, fmt::Error>
+where
+    F: FnMut(&mut dyn Write) -> fmt::Result,
 {
-    match value {
-        Value::AssociativeArray(value) if !value.is_empty() => {
-            push_sep(dst);
-            expand_varspec_assoc(dst, operator_table, varspec, value);
-        }
-        Value::List(value) if !value.is_empty() => {
-            push_sep(dst);
-            expand_varspec_list(dst, operator_table, varspec, value);
+    let mut defined = false;
+    let mut push_item_sep = |dst: &mut dyn Write| {
+        if defined {
+            dst.write_char(',')
+        } else {
+            defined = true;
+            push_sep(dst)?;
+            push_name(dst, operator_table, varspec, false)
         }
-        Value::String(value) => {
-            push_sep(dst);
-            expand_varspec_string(dst, operator_table, varspec, value);
+    };
+    match value {
+        ValueRef::AssociativeArray(value) => value.for_each_entry(&mut |k, v| {
+            with_scalar(v, |v| {
+                push_item_sep(dst)?;
+                (operator_table.allow)(dst, k)?;
+                dst.write_char(',')?;
+                (operator_table.allow)(dst, v)
+            })
+            .unwrap_or(Ok(()))
+        })?,
+        ValueRef::List(value) => value.for_each_item(&mut |v| {
+            with_scalar(v, |v| {
+                push_item_sep(dst)?;
+                (operator_table.allow)(dst, v)
+            })
+            .unwrap_or(Ok(()))
+        })?,
+        value => {
+            return with_scalar(value, |v| {
+                push_sep(dst)?;
+                expand_varspec_string(dst, operator_table, varspec, v)?;
+                Ok(true)
+            })
+            .unwrap_or(Ok(false));
         }
-        _ => {}
     }
-}
-
-fn expand_varspec_assoc(
-    dst: &mut String,
-    operator_table: &OperatorTable,
-    varspec: &Varspec,
-    value: &[(String, String)],
-) {
-    push_name(dst, operator_table, varspec, false);
-    let mut push_sep = make_push_sep("", ",");
-    expand_assoc(dst, &operator_table.allow, &mut push_sep, ",", value);
-}
-
-fn expand_varspec_list(
-    dst: &mut String,
-    operator_table: &OperatorTable,
-    varspec: &Varspec,
-    value: &[String],
-) {
-    push_name(dst, operator_table, varspec, false);
-    let mut push_sep = make_push_sep("", ",");
-    expand_list(dst, &operator_table.allow, &mut push_sep, value);
+    Ok(defined)
 }
 
 fn expand_varspec_string(
-    dst: &mut String,
+    dst: &mut dyn Write,
     operator_table: &OperatorTable,
     varspec: &Varspec,
     mut value: &str,
-) {
+) -> fmt::Result {
     let empty = value.is_empty();
-    push_name(dst, operator_table, varspec, empty);
+    push_name(dst, operator_table, varspec, empty)?;
     if !empty {
         if let Some(ModifierLevel4::Prefix(size)) = varspec.modifier_level4 {
             let i = value
                 .char_indices()
-                .map(|(i, _)| i)
-                .take(size)
-                .last()
-                .unwrap_or(0);
-            value = &value[..=i];
+                .nth(size)
+                .map_or(value.len(), |(i, _)| i);
+            value = &value[..i];
         }
-        (operator_table.allow)(dst, value);
+        (operator_table.allow)(dst, value)?;
     }
+    Ok(())
 }
 
-fn push_name(dst: &mut String, operator_table: &OperatorTable, varspec: &Varspec, empty: bool) {
+fn push_name(
+    dst: &mut dyn Write,
+    operator_table: &OperatorTable,
+    varspec: &Varspec,
+    empty: bool,
+) -> fmt::Result {
     if operator_table.named {
-        push_literal(dst, &varspec.varname);
+        push_literal(dst, &varspec.varname)?;
         if empty {
-            dst.push_str(operator_table.ifemp);
+            dst.write_str(operator_table.ifemp)?;
         } else {
-            dst.push('=')
+            dst.write_char('=')?;
         }
     }
+    Ok(())
 }
 
-fn expand_assoc<F>(
-    dst: &mut String,
-    push_allow: &PushAllow,
-    push_sep: &mut F,
-    kv_sep: &str,
-    value: &[(String, String)],
-) where
-    F: FnMut(&mut String),
-{
-    for (k, v) in value {
-        push_sep(dst);
-        push_allow(dst, k);
-        dst.push_str(kv_sep);
-        push_allow(dst, v);
-    }
-}
-
-fn expand_list<F>(dst: &mut String, push_allow: &PushAllow, push_sep: &mut F, value: &[String])
-where
-    F: FnMut(&mut String),
-{
-    for v in value {
-        push_sep(dst);
-        push_allow(dst, v);
+/// Pushes `=value`, or `ifemp` for an empty value, after the name of an exploded item.
+fn push_value(dst: &mut dyn Write, operator_table: &OperatorTable, value: &str) -> fmt::Result {
+    if value.is_empty() {
+        dst.write_str(operator_table.ifemp)
+    } else {
+        dst.write_char('=')?;
+        (operator_table.allow)(dst, value)
     }
 }
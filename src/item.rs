@@ -1,34 +1,316 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::FusedIterator;
+use std::slice;
+
+/// An RFC 6570 level, see section 1.2. Each level supports the templates of the levels below it.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+    /// Simple string expansion of single variables, e.g. `{x}`.
+    One = 1,
+    /// Reserved and fragment expansion, e.g. `{+x}` and `{#x}`.
+    Two,
+    /// Variable lists and the other operators, e.g. `{x,y}` and `{?x}`.
+    Three,
+    /// Prefix and explode modifiers, e.g. `{x:3}` and `{x*}`.
+    Four,
+}
+
+/// A part of a parsed template: either literal text or an expression in braces.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Item {
     Literal(String),
     Expression(Expression),
 }
 
+/// An expression such as `{?x,y*}`: an optional operator and a list of variables.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Expression {
-    pub operator: Option<Operator>,
-    pub variable_list: Vec<Varspec>,
+    pub(crate) operator: Option<Operator>,
+    pub(crate) variable_list: Vec<Varspec>,
 }
 
+/// A variable in an expression, with its optional level 4 modifier.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Varspec {
-    pub varname: String,
-    pub modifier_level4: Option<ModifierLevel4>,
+    pub(crate) varname: String,
+    pub(crate) modifier_level4: Option<ModifierLevel4>,
 }
 
+/// An expression operator, see RFC 6570 section 3.2.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Operator {
+    /// `{+x}`
     Reserved,
+    /// `{#x}`
     Fragment,
+    /// `{.x}`
     Label,
+    /// `{/x}`
     PathSegment,
+    /// `{;x}`
     PathParameter,
+    /// `{?x}`
     FormQuery,
+    /// `{&x}`
     FormContinuation,
 }
 
+/// A value modifier, see RFC 6570 section 2.4.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ModifierLevel4 {
+    /// `{x:3}`, the maximum number of characters to expand.
     Prefix(usize),
+    /// `{x*}`
     Explode,
 }
+
+/// An iterator over the items of a template, see [`UriTemplate::items`](crate::UriTemplate::items).
+#[derive(Clone, Debug)]
+pub struct Items<'a>(slice::Iter<'a, Item>);
+
+/// An iterator over the expressions of a template, see
+/// [`UriTemplate::expressions`](crate::UriTemplate::expressions).
+#[derive(Clone, Debug)]
+pub struct Expressions<'a>(Items<'a>);
+
+/// An iterator over the variables of every expression of a template, in order of appearance, see
+/// [`UriTemplate::variables`](crate::UriTemplate::variables).
+#[derive(Clone, Debug)]
+pub struct Varspecs<'a> {
+    expressions: Expressions<'a>,
+    varspecs: slice::Iter<'a, Varspec>,
+}
+
+impl Item {
+    pub fn as_literal(&self) -> Option<&str> {
+        match self {
+            Item::Literal(literal) => Some(literal),
+            Item::Expression(_) => None,
+        }
+    }
+
+    pub fn as_expression(&self) -> Option<&Expression> {
+        match self {
+            Item::Literal(_) => None,
+            Item::Expression(expression) => Some(expression),
+        }
+    }
+}
+
+impl Expression {
+    pub(crate) fn new(operator: Option<Operator>, variable_list: Vec<Varspec>) -> Self {
+        Expression {
+            operator,
+            variable_list,
+        }
+    }
+
+    /// The operator, or `None` for simple string expansion.
+    pub fn operator(&self) -> Option<Operator> {
+        self.operator
+    }
+
+    pub fn varspecs(&self) -> &[Varspec] {
+        &self.variable_list
+    }
+
+    /// The lowest RFC 6570 level that supports this expression: 4 with modifiers, 3 with several
+    /// variables, otherwise the level of the operator, or 1 without one.
+    pub fn level(&self) -> Level {
+        if self
+            .variable_list
+            .iter()
+            .any(|v| v.modifier_level4.is_some())
+        {
+            Level::Four
+        } else if self.variable_list.len() > 1 {
+            Level::Three
+        } else {
+            self.operator.map_or(Level::One, Operator::level)
+        }
+    }
+}
+
+impl Varspec {
+    /// The variable name as written in the template, including any pct-encoded triplets.
+    pub fn varname(&self) -> &str {
+        &self.varname
+    }
+
+    pub fn modifier(&self) -> Option<ModifierLevel4> {
+        self.modifier_level4
+    }
+
+    pub fn is_exploded(&self) -> bool {
+        self.modifier_level4 == Some(ModifierLevel4::Explode)
+    }
+
+    /// The maximum number of characters to expand, if the variable has a prefix modifier.
+    pub fn prefix(&self) -> Option<usize> {
+        match self.modifier_level4 {
+            Some(ModifierLevel4::Prefix(size)) => Some(size),
+            _ => None,
+        }
+    }
+}
+
+impl Operator {
+    /// The character that introduces this operator in an expression.
+    pub fn as_char(self) -> char {
+        match self {
+            Operator::Reserved => '+',
+            Operator::Fragment => '#',
+            Operator::Label => '.',
+            Operator::PathSegment => '/',
+            Operator::PathParameter => ';',
+            Operator::FormQuery => '?',
+            Operator::FormContinuation => '&',
+        }
+    }
+
+    /// The lowest RFC 6570 level that supports this operator, 2 or 3.
+    pub fn level(self) -> Level {
+        match self {
+            Operator::Reserved | Operator::Fragment => Level::Two,
+            _ => Level::Three,
+        }
+    }
+}
+
+impl From<Level> for u8 {
+    fn from(level: Level) -> Self {
+        level as u8
+    }
+}
+
+impl TryFrom<u8> for Level {
+    type Error = u8;
+
+    /// Fails with `level` if it is not between 1 and 4.
+    fn try_from(level: u8) -> Result<Self, u8> {
+        match level {
+            1 => Ok(Level::One),
+            2 => Ok(Level::Two),
+            3 => Ok(Level::Three),
+            4 => Ok(Level::Four),
+            _ => Err(level),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", u8::from(*self))
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Item::Literal(literal) => f.write_str(literal),
+            Item::Expression(expression) => expression.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("{")?;
+        if let Some(operator) = self.operator {
+            operator.fmt(f)?;
+        }
+        for (i, varspec) in self.variable_list.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            varspec.fmt(f)?;
+        }
+        f.write_str("}")
+    }
+}
+
+impl fmt::Display for Varspec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.varname)?;
+        match self.modifier_level4 {
+            None => Ok(()),
+            Some(ModifierLevel4::Prefix(size)) => write!(f, ":{}", size),
+            Some(ModifierLevel4::Explode) => f.write_str("*"),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_char())
+    }
+}
+
+impl<'a> Items<'a> {
+    pub(crate) fn new(items: &'a [Item]) -> Self {
+        Items(items.iter())
+    }
+}
+
+impl<'a> Iterator for Items<'a> {
+    type Item = &'a Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Items<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+impl ExactSizeIterator for Items<'_> {}
+
+impl FusedIterator for Items<'_> {}
+
+impl<'a> Expressions<'a> {
+    pub(crate) fn new(items: &'a [Item]) -> Self {
+        Expressions(Items::new(items))
+    }
+}
+
+impl<'a> Iterator for Expressions<'a> {
+    type Item = &'a Expression;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(Item::as_expression)
+    }
+}
+
+impl FusedIterator for Expressions<'_> {}
+
+impl<'a> Varspecs<'a> {
+    pub(crate) fn new(items: &'a [Item]) -> Self {
+        Varspecs {
+            expressions: Expressions::new(items),
+            varspecs: [].iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for Varspecs<'a> {
+    type Item = &'a Varspec;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(varspec) = self.varspecs.next() {
+                return Some(varspec);
+            }
+            self.varspecs = self.expressions.next()?.variable_list.iter();
+        }
+    }
+}
+
+impl FusedIterator for Varspecs<'_> {}
@@ -1,36 +1,127 @@
+mod config;
 mod encoding;
+mod env;
 mod expand;
 mod item;
+pub mod lint;
+mod matching;
+mod nesting;
 mod parse;
-
-use std::borrow::Borrow;
-use std::collections::HashMap;
-
-use crate::expand::expand_items;
-use crate::item::Item;
+mod partial;
+mod pattern;
+mod position;
+mod router;
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod value_ref;
+mod variables;
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use crate::expand::{check_defined, expand_items, expand_items_io};
+use crate::matching::match_items;
+use crate::nesting::flatten;
 use crate::parse::parse_template;
+use crate::partial::partial_expand_items;
+use crate::pattern::to_regex_pattern;
+use crate::position::variable_usages;
+
+pub use crate::config::{ConfigError, ConfigVariables};
+pub use crate::env::EnvVariables;
+pub use crate::expand::{ExpandError, ExpandPolicy};
+pub use crate::item::{
+    Expression, Expressions, Item, Items, Level, ModifierLevel4, Operator, Varspec, Varspecs,
+};
+pub use crate::matching::MatchError;
+pub use crate::nesting::NestingPolicy;
+pub use crate::parse::{LiteralMode, ParseError, ParseErrorKind, ParseErrors};
+pub use crate::partial::PartialExpandError;
+pub use crate::position::{Position, VariableUsage};
+pub use crate::router::{Match, RouteConflict, Router};
+#[cfg(feature = "serde")]
+pub use crate::ser::{to_variables, SerializeError, Serialized};
+pub use crate::value_ref::{
+    AsValueRef, AssociativeArray, IterAssociativeArray, IterList, List, ValueRef,
+};
+pub use crate::variables::{chain, from_fn, Chain, ChainValue, FromFn};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct UriTemplate(Vec<Item>);
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+/// Configures how template strings are parsed.
+///
+/// By default parsing is strict and the first malformed expression is reported as a
+/// [`ParseError`]. In lenient mode malformed expressions are kept as literal text, which is the
+/// behavior of [`UriTemplate::parse`].
+#[derive(Clone, Debug)]
+pub struct Parser {
+    lenient: bool,
+    literals: LiteralMode,
+    max_level: Level,
+}
+
+/// The value of a variable.
+///
+/// Integers and booleans expand to their `Display` form, e.g. `-42` and `true`, and so do finite
+/// floats, which never use an exponent, e.g. `2.5` and `0.001`. Infinite and NaN floats are
+/// undefined, like `Undefined` itself, empty lists and empty associative arrays.
+///
+/// `Undefined` expands like a missing variable but still counts as set, e.g. for
+/// [`UriTemplate::try_expand`] and [`UriTemplate::partial_expand`].
+///
+/// Lists and associative arrays nested in a list or associative array are undefined items, and
+/// are skipped, unless the value is first flattened with [`Value::flatten`].
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
-    AssociativeArray(Vec<(String, String)>),
-    List(Vec<String>),
+    AssociativeArray(Vec<(String, Value)>),
+    List(Vec<Value>),
     String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Undefined,
 }
 
 pub trait Variables<'a, B>
 where
-    B: Borrow<Value>,
+    B: AsValueRef,
 {
     fn get(&'a self, k: &'a str) -> Option<B>;
 }
 
+/// Converts a value to a variable value, or `None` if the variable is undefined.
+///
+/// This is how `#[derive(Variables)]` from the `uri-template-macros` crate converts struct
+/// fields: strings, numbers and booleans become the matching scalars, `Vec<T>` and slices become
+/// lists, and maps become associative arrays.
+pub trait ToValue {
+    fn to_value(&self) -> Option<Value>;
+}
+
+/// Sets variables one by one and expands a template with them.
+///
+/// Variables that are not set are looked up in the base source, if the expander was created with
+/// [`UriTemplate::expander_with`].
 #[derive(Debug)]
-pub struct Expander<'a> {
+pub struct Expander<'a, S = ()>
+where
+    S: ?Sized,
+{
     uri_template: &'a UriTemplate,
-    variables: HashMap<String, Value>,
+    variables: HashMap<String, Binding<'a>>,
+    base: &'a S,
+}
+
+enum Binding<'a> {
+    Owned(Value),
+    Borrowed(ValueRef<'a>),
+    Lazy(Box<dyn AsValueRef + 'a>),
 }
 
 impl UriTemplate {
@@ -38,30 +129,286 @@ impl UriTemplate {
     where
         S: AsRef<str>,
     {
-        let template = template.as_ref();
-        let items = parse_template(template);
-        UriTemplate(items)
+        Parser::new()
+            .lenient(true)
+            .parse(template)
+            .expect("lenient parsing never fails")
+    }
+
+    pub fn try_parse<S>(template: S) -> Result<Self, ParseError>
+    where
+        S: AsRef<str>,
+    {
+        Parser::new().parse(template)
+    }
+
+    pub fn try_parse_all<S>(template: S) -> Result<Self, ParseErrors>
+    where
+        S: AsRef<str>,
+    {
+        Parser::new().parse_all(template)
     }
 
     pub fn expand<'a, V, B>(&'a self, variables: &'a V) -> String
     where
-        V: Variables<'a, B>,
-        B: Borrow<Value>,
+        V: Variables<'a, B> + ?Sized,
+        B: AsValueRef,
+    {
+        let mut dst = String::new();
+        self.expand_to(variables, &mut dst)
+            .expect("writing to a String never fails");
+        dst
+    }
+
+    /// Expands into `dst` instead of a new `String`.
+    pub fn expand_to<'a, V, B, W>(&'a self, variables: &'a V, dst: &mut W) -> fmt::Result
+    where
+        V: Variables<'a, B> + ?Sized,
+        B: AsValueRef,
+        W: fmt::Write + ?Sized,
+    {
+        expand_items(&self.0, variables, &mut &mut *dst)
+    }
+
+    /// Expands into `dst` as UTF-8, e.g. straight into a response body. The expansion is written
+    /// in small pieces, so `dst` should be buffered.
+    pub fn expand_to_io<'a, V, B, W>(&'a self, variables: &'a V, dst: &mut W) -> io::Result<()>
+    where
+        V: Variables<'a, B> + ?Sized,
+        B: AsValueRef,
+        W: io::Write + ?Sized,
+    {
+        expand_items_io(&self.0, variables, &mut &mut *dst)
+    }
+
+    /// Like [`UriTemplate::expand`], but fails with every variable that `policy` requires and that
+    /// is undefined.
+    pub fn try_expand<'a, V, B>(
+        &'a self,
+        variables: &'a V,
+        policy: ExpandPolicy,
+    ) -> Result<String, ExpandError>
+    where
+        V: Variables<'a, B> + ?Sized,
+        B: AsValueRef,
+    {
+        check_defined(&self.0, variables, policy)?;
+        Ok(self.expand(variables))
+    }
+
+    /// Expands the variables that are defined in `variables` and returns a template for the
+    /// others, such that expanding it later gives the same result as expanding this template with
+    /// every variable at once.
+    ///
+    /// Within an expression, the bound variables must come before the unbound ones. The unbound
+    /// variables of a `{?x,y}` expression can follow defined ones, as they become `{&y}`, but not
+    /// those of `{x,y}`, `{+x,y}` or `{#x,y}`.
+    pub fn partial_expand<'a, V, B>(
+        &'a self,
+        variables: &'a V,
+    ) -> Result<UriTemplate, PartialExpandError>
+    where
+        V: Variables<'a, B> + ?Sized,
+        B: AsValueRef,
     {
-        expand_items(&self.0, variables)
+        partial_expand_items(&self.0, variables).map(UriTemplate)
     }
 
-    pub fn expander(&self) -> Expander {
+    /// The literals and expressions of this template, in order.
+    pub fn items(&self) -> Items<'_> {
+        Items::new(&self.0)
+    }
+
+    pub fn expressions(&self) -> Expressions<'_> {
+        Expressions::new(&self.0)
+    }
+
+    /// The variables of every expression, in order of appearance. A variable used in several
+    /// expressions is yielded once per use.
+    pub fn variables(&self) -> Varspecs<'_> {
+        Varspecs::new(&self.0)
+    }
+
+    /// The lowest RFC 6570 level that supports this template, from 1 for simple string expansion
+    /// of single variables to 4 for modifiers, see [`Expression::level`].
+    pub fn level(&self) -> Level {
+        self.expressions()
+            .map(Expression::level)
+            .max()
+            .unwrap_or(Level::One)
+    }
+
+    /// The distinct variable names, in order of first appearance.
+    pub fn variable_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for varspec in self.variables() {
+            if !names.contains(&varspec.varname()) {
+                names.push(varspec.varname());
+            }
+        }
+        names
+    }
+
+    /// Every use of a variable, in order of appearance, with its operator, modifier, position and
+    /// span.
+    ///
+    /// A variable is in the query from a literal `?` or a query expression on, and in the
+    /// fragment from a literal `#` or a fragment expression on. Spans are byte offsets in the
+    /// string form of the template, which is the source text for parsed templates.
+    pub fn variable_usages(&self) -> Vec<VariableUsage<'_>> {
+        variable_usages(&self.0)
+    }
+
+    /// Extracts the variables for which this template expands to `uri`.
+    ///
+    /// Captured values are percent-decoded. A raw comma in an unexploded value is read as a list
+    /// separator, and exploded values whose items all contain `=` are read as associative arrays.
+    /// When a variable list is shorter than its expression, the leading variables are bound.
+    pub fn match_uri(&self, uri: &str) -> Result<Vec<(String, Value)>, MatchError> {
+        match_items(&self.0, uri)
+    }
+
+    /// Compiles this template to an anchored regular expression with one named capture group per
+    /// variable.
+    ///
+    /// Group names are the variable names with characters other than ASCII letters and digits
+    /// replaced by `_`, suffixed with `_2`, `_3`, ... when a name repeats. Exploded variables
+    /// capture their whole expansion, including keys and separators. The pattern is meant for
+    /// selecting candidate templates in bulk and may accept URIs that [`UriTemplate::match_uri`]
+    /// rejects, e.g. for expressions with several variables.
+    pub fn to_regex_pattern(&self) -> String {
+        to_regex_pattern(&self.0)
+    }
+
+    pub fn expander(&self) -> Expander<'_> {
+        self.expander_with(&())
+    }
+
+    /// Creates an expander that looks up the variables it does not set in `base`.
+    pub fn expander_with<'a, S>(&'a self, base: &'a S) -> Expander<'a, S>
+    where
+        S: ?Sized,
+    {
         Expander {
             uri_template: self,
             variables: HashMap::new(),
+            base,
         }
     }
 }
 
-impl Expander<'_> {
-    pub fn expand(&self) -> String {
-        self.uri_template.expand(&self.variables)
+/// Writes the canonical template text, which parses back to an equal template.
+impl fmt::Display for UriTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|item| item.fmt(f))
+    }
+}
+
+impl FromStr for UriTemplate {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UriTemplate::try_parse(s)
+    }
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser {
+            lenient: false,
+            literals: LiteralMode::default(),
+            max_level: Level::Four,
+        }
+    }
+
+    pub fn lenient(&mut self, lenient: bool) -> &mut Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sets the highest RFC 6570 level to accept, by default [`Level::Four`]. Operators, variable
+    /// lists and modifiers above it are errors of kind [`ParseErrorKind::UnsupportedLevel`], e.g.
+    /// `{.x}`, `{x,y}` and `{x:3}` for level 2.
+    pub fn max_level(&mut self, max_level: Level) -> &mut Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Sets how literal text is treated, by default [`LiteralMode::Verbatim`]. With
+    /// [`LiteralMode::Encode`] the parsed template holds the encoded literals, so it no longer
+    /// displays as the input string.
+    pub fn literals(&mut self, literals: LiteralMode) -> &mut Self {
+        self.literals = literals;
+        self
+    }
+
+    pub fn parse<S>(&self, template: S) -> Result<UriTemplate, ParseError>
+    where
+        S: AsRef<str>,
+    {
+        self.parse_all(template).map_err(|e| e.errors()[0].clone())
+    }
+
+    /// Like [`Parser::parse`], but reports every error in the template instead of only the first.
+    pub fn parse_all<S>(&self, template: S) -> Result<UriTemplate, ParseErrors>
+    where
+        S: AsRef<str>,
+    {
+        let template = template.as_ref();
+        let (items, errors) = parse_template(template, self.literals, self.max_level);
+        if self.lenient || errors.is_empty() {
+            Ok(UriTemplate(items))
+        } else {
+            Err(ParseErrors::new(template, errors))
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+impl<'a, S> Expander<'a, S>
+where
+    S: ?Sized,
+{
+    pub fn expand<'b, B>(&'b self) -> String
+    where
+        S: Variables<'b, B>,
+        B: AsValueRef,
+    {
+        self.uri_template.expand(self)
+    }
+
+    pub fn try_expand<'b, B>(&'b self, policy: ExpandPolicy) -> Result<String, ExpandError>
+    where
+        S: Variables<'b, B>,
+        B: AsValueRef,
+    {
+        self.uri_template.try_expand(self, policy)
+    }
+
+    /// Sets a variable to a borrowed value, which is expanded without being copied.
+    pub fn set_ref<K, V>(&mut self, k: K, v: &'a V) -> &mut Self
+    where
+        K: Into<String>,
+        V: AsValueRef + ?Sized,
+    {
+        let k = k.into();
+        let v = Binding::Borrowed(v.as_value_ref());
+        self.variables.insert(k, v);
+        self
+    }
+
+    pub fn set_value<K>(&mut self, k: K, v: Value) -> &mut Self
+    where
+        K: Into<String>,
+    {
+        let k = k.into();
+        self.variables.insert(k, Binding::Owned(v));
+        self
     }
 
     pub fn set_assoc<K1, V1, K2, V2>(&mut self, k1: K1, iter: V1) -> &mut Self
@@ -73,8 +420,7 @@ impl Expander<'_> {
     {
         let k1 = k1.into();
         let v1 = Value::from_assoc(iter);
-        self.variables.insert(k1, v1);
-        self
+        self.set_value(k1, v1)
     }
 
     pub fn set_list<K1, V1, V2>(&mut self, k: K1, iter: V1) -> &mut Self
@@ -83,21 +429,47 @@ impl Expander<'_> {
         V1: IntoIterator<Item = V2>,
         V2: Into<String>,
     {
-        let k = k.into();
         let v = Value::from_list(iter);
+        self.set_value(k, v)
+    }
+
+    /// Sets a variable to a list that is read from `iter` as it is expanded, see [`IterList`].
+    pub fn set_list_iter<K, I>(&mut self, k: K, iter: I) -> &mut Self
+    where
+        K: Into<String>,
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: AsValueRef,
+    {
+        let k = k.into();
+        let v = Binding::Lazy(Box::new(IterList::new(iter)));
         self.variables.insert(k, v);
         self
     }
 
+    /// Sets a variable to an associative array that is read from `iter` as it is expanded, see
+    /// [`IterAssociativeArray`].
+    pub fn set_assoc_iter<K1, I, K2, V2>(&mut self, k1: K1, iter: I) -> &mut Self
+    where
+        K1: Into<String>,
+        I: IntoIterator<Item = (K2, V2)>,
+        I::IntoIter: 'a,
+        K2: AsRef<str>,
+        V2: AsValueRef,
+    {
+        let k1 = k1.into();
+        let v1 = Binding::Lazy(Box::new(IterAssociativeArray::new(iter)));
+        self.variables.insert(k1, v1);
+        self
+    }
+
     pub fn set_string<K, V>(&mut self, k: K, v: V) -> &mut Self
     where
         K: Into<String>,
         V: Into<String>,
     {
-        let k = k.into();
         let v = Value::from_string(v);
-        self.variables.insert(k, v);
-        self
+        self.set_value(k, v)
     }
 }
 
@@ -110,7 +482,7 @@ impl Value {
     {
         Value::AssociativeArray(
             iter.into_iter()
-                .map(|(k, v)| (k.into(), v.into()))
+                .map(|(k, v)| (k.into(), Value::String(v.into())))
                 .collect(),
         )
     }
@@ -120,7 +492,7 @@ impl Value {
         I: IntoIterator<Item = V>,
         V: Into<String>,
     {
-        Value::List(iter.into_iter().map(Into::into).collect())
+        Value::List(iter.into_iter().map(Value::from_string).collect())
     }
 
     pub fn from_string<S>(s: S) -> Value
@@ -129,22 +501,203 @@ impl Value {
     {
         Value::String(s.into())
     }
+
+    /// Flattens nested lists and associative arrays into a single associative array, with keys
+    /// made of the path to each item, e.g. `{"a": {"b": "c"}}` becomes `{"a.b": "c"}` or
+    /// `{"a[b]": "c"}`. List items are keyed by their index.
+    ///
+    /// Values without nesting are returned unchanged.
+    pub fn flatten(&self, policy: NestingPolicy) -> Value {
+        flatten(self, policy)
+    }
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Option<Value> {
+        Some(self.clone())
+    }
+}
+
+macro_rules! impl_to_value {
+    ($($t:ty => |$v:ident| $e:expr),* $(,)?) => {
+        $(
+            impl ToValue for $t {
+                fn to_value(&self) -> Option<Value> {
+                    let $v = self;
+                    Some($e)
+                }
+            }
+        )*
+    };
+}
+
+impl_to_value!(
+    str => |v| Value::String(v.to_string()),
+    String => |v| Value::String(v.clone()),
+    char => |v| Value::String(v.to_string()),
+    bool => |v| Value::Boolean(*v),
+    i8 => |v| Value::Integer(i64::from(*v)),
+    i16 => |v| Value::Integer(i64::from(*v)),
+    i32 => |v| Value::Integer(i64::from(*v)),
+    i64 => |v| Value::Integer(*v),
+    u8 => |v| Value::Integer(i64::from(*v)),
+    u16 => |v| Value::Integer(i64::from(*v)),
+    u32 => |v| Value::Integer(i64::from(*v)),
+    f32 => |v| Value::Float(f64::from(*v)),
+    f64 => |v| Value::Float(*v),
+);
+
+/// Integers that may not fit in an `i64` fall back to their string form.
+macro_rules! impl_to_value_wide_integer {
+    ($($t:ty),*) => {
+        $(
+            impl ToValue for $t {
+                fn to_value(&self) -> Option<Value> {
+                    Some(match i64::try_from(*self) {
+                        Ok(v) => Value::Integer(v),
+                        Err(_) => Value::String(self.to_string()),
+                    })
+                }
+            }
+        )*
+    };
 }
 
-impl<'a> Variables<'a, &'a Value> for Vec<(String, Value)> {
-    fn get(&'a self, k: &str) -> Option<&Value> {
-        self.iter().find(|(k1, _)| k == k1).map(|(_, v1)| v1)
+impl_to_value_wide_integer!(i128, isize, u64, u128, usize);
+
+impl<T> ToValue for &T
+where
+    T: ToValue + ?Sized,
+{
+    fn to_value(&self) -> Option<Value> {
+        (**self).to_value()
     }
 }
 
-impl<'a> Variables<'a, &'a Value> for HashMap<String, Value> {
-    fn get(&self, k: &str) -> Option<&Value> {
-        self.get(k)
+impl<T> ToValue for Option<T>
+where
+    T: ToValue,
+{
+    fn to_value(&self) -> Option<Value> {
+        self.as_ref().and_then(ToValue::to_value)
+    }
+}
+
+impl<T> ToValue for [T]
+where
+    T: ToValue,
+{
+    fn to_value(&self) -> Option<Value> {
+        Some(Value::List(self.iter().map(item_value).collect()))
+    }
+}
+
+impl<T> ToValue for Vec<T>
+where
+    T: ToValue,
+{
+    fn to_value(&self) -> Option<Value> {
+        self.as_slice().to_value()
+    }
+}
+
+impl<K, V, S> ToValue for HashMap<K, V, S>
+where
+    K: fmt::Display,
+    V: ToValue,
+{
+    fn to_value(&self) -> Option<Value> {
+        Some(Value::AssociativeArray(
+            self.iter()
+                .map(|(k, v)| (k.to_string(), item_value(v)))
+                .collect(),
+        ))
+    }
+}
+
+impl<K, V> ToValue for BTreeMap<K, V>
+where
+    K: fmt::Display,
+    V: ToValue,
+{
+    fn to_value(&self) -> Option<Value> {
+        Some(Value::AssociativeArray(
+            self.iter()
+                .map(|(k, v)| (k.to_string(), item_value(v)))
+                .collect(),
+        ))
+    }
+}
+
+/// Converts an item of a list or associative array, where `None` is an undefined item.
+fn item_value<T>(v: &T) -> Value
+where
+    T: ToValue + ?Sized,
+{
+    v.to_value().unwrap_or(Value::Undefined)
+}
+
+impl fmt::Debug for Binding<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Owned(v) => f.debug_tuple("Owned").field(v).finish(),
+            Binding::Borrowed(v) => f.debug_tuple("Borrowed").field(v).finish(),
+            Binding::Lazy(v) => f.debug_tuple("Lazy").field(&v.as_value_ref()).finish(),
+        }
+    }
+}
+
+impl AsValueRef for Binding<'_> {
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        match self {
+            Binding::Owned(v) => v.as_value_ref(),
+            Binding::Borrowed(v) => *v,
+            Binding::Lazy(v) => v.as_value_ref(),
+        }
+    }
+}
+
+/// Variables set on the expander take precedence over the base source.
+impl<'a, S, B> Variables<'a, ChainValue<ValueRef<'a>, B>> for Expander<'_, S>
+where
+    S: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<ChainValue<ValueRef<'a>, B>> {
+        match self.variables.get(k) {
+            Some(binding) => Some(ChainValue::First(binding.as_value_ref())),
+            None => self.base.get(k).map(ChainValue::Second),
+        }
+    }
+}
+
+/// Constructors for the code generated by the `uri-template-macros` crate, which builds templates
+/// that are known to be valid without parsing them again. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{Expression, Item, ModifierLevel4, Operator, UriTemplate, Varspec};
+
+    pub fn uri_template(items: Vec<Item>) -> UriTemplate {
+        UriTemplate(items)
+    }
+
+    pub fn expression(operator: Option<Operator>, variable_list: Vec<Varspec>) -> Expression {
+        Expression::new(operator, variable_list)
+    }
+
+    pub fn varspec(varname: String, modifier_level4: Option<ModifierLevel4>) -> Varspec {
+        Varspec {
+            varname,
+            modifier_level4,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
     use super::*;
 
     #[test]
@@ -301,6 +854,771 @@ mod tests {
         assert_eq!(left, "{%0}");
     }
 
+    fn try_parse_error(template: &str) -> (ParseErrorKind, std::ops::Range<usize>) {
+        let error = UriTemplate::try_parse(template).unwrap_err();
+        (error.kind(), error.span())
+    }
+
+    #[test]
+    fn test_try_parse() {
+        let left = UriTemplate::try_parse("x{y}{+z:3}{/w*}").unwrap();
+        assert_eq!(left, UriTemplate::parse("x{y}{+z:3}{/w*}"));
+    }
+
+    #[test]
+    fn test_try_parse_errors() {
+        use ParseErrorKind::*;
+
+        assert_eq!(try_parse_error("x{}"), (EmptyExpression, 1..3));
+        assert_eq!(try_parse_error("x{y"), (UnterminatedExpression, 1..3));
+        assert_eq!(try_parse_error("{!x}"), (UnknownOperator, 1..2));
+        assert_eq!(try_parse_error("{x,,y}"), (InvalidVarname, 3..3));
+        assert_eq!(try_parse_error("{?x~}"), (InvalidVarname, 3..4));
+        assert_eq!(try_parse_error("{?.}"), (InvalidVarname, 2..3));
+        assert_eq!(try_parse_error("{x:1y}"), (InvalidPrefix, 2..5));
+        assert_eq!(try_parse_error("{x:10000}"), (InvalidPrefix, 2..8));
+        assert_eq!(try_parse_error("{x*y}"), (MisplacedExplode, 2..3));
+        assert_eq!(try_parse_error("{x:1*}"), (MisplacedExplode, 4..5));
+        assert_eq!(try_parse_error("{%0}"), (InvalidPctEncoded, 1..3));
+        assert_eq!(try_parse_error("{x%zz}"), (InvalidPctEncoded, 2..5));
+    }
+
+    #[test]
+    fn test_try_parse_all() {
+        let errors = UriTemplate::try_parse_all("/users/{!id}/{x,,y}{z:0}{").unwrap_err();
+        let left: Vec<_> = errors
+            .errors()
+            .iter()
+            .map(|e| (e.kind(), e.span()))
+            .collect();
+        assert_eq!(
+            left,
+            [
+                (ParseErrorKind::UnknownOperator, 8..9),
+                (ParseErrorKind::InvalidVarname, 16..16),
+                (ParseErrorKind::InvalidPrefix, 21..23),
+                (ParseErrorKind::UnterminatedExpression, 24..25),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_display() {
+        let errors = UriTemplate::try_parse_all("/users/{!id}{x:1y}").unwrap_err();
+        let right = "\
+error: unknown operator
+  |
+  | /users/{!id}{x:1y}
+  |         ^
+
+error: invalid prefix length
+  |
+  | /users/{!id}{x:1y}
+  |               ^^^";
+        assert_eq!(errors.to_string(), right);
+    }
+
+    #[test]
+    fn test_parser_lenient() {
+        let left = Parser::new().lenient(true).parse("{!x}").unwrap();
+        assert_eq!(left, UriTemplate::parse("{!x}"));
+    }
+
+    #[test]
+    fn test_parser_literals_validate() {
+        let left = Parser::new()
+            .literals(LiteralMode::Validate)
+            .parse_all("/a b/{x}/\"%41%4|")
+            .unwrap_err();
+        let left: Vec<_> = left.errors().iter().map(|e| (e.kind(), e.span())).collect();
+        let right = vec![
+            (ParseErrorKind::InvalidLiteral, 2..3),
+            (ParseErrorKind::InvalidLiteral, 9..10),
+            (ParseErrorKind::InvalidLiteral, 13..14),
+            (ParseErrorKind::InvalidLiteral, 15..16),
+        ];
+        assert_eq!(left, right);
+
+        let left = Parser::new()
+            .literals(LiteralMode::Validate)
+            .parse("/caf\u{e9}/{x}?a=%20");
+        assert!(left.is_ok());
+    }
+
+    #[test]
+    fn test_parser_literals_encode() {
+        let uri_template = Parser::new()
+            .literals(LiteralMode::Encode)
+            .parse("/a b/caf\u{e9}/{x}%41%4<?q=1")
+            .unwrap();
+        assert_eq!(
+            uri_template.to_string(),
+            "/a%20b/caf%C3%A9/{x}%41%254%3C?q=1"
+        );
+        let left = uri_template.expander().set_string("x", "y").expand();
+        assert_eq!(left, "/a%20b/caf%C3%A9/y%41%254%3C?q=1");
+    }
+
+    #[test]
+    fn test_parser_max_level() {
+        let left = Parser::new()
+            .max_level(Level::Two)
+            .parse_all("{+a}{.b}{?c,d}{e:3}{f*}")
+            .unwrap_err();
+        let left: Vec<_> = left.errors().iter().map(|e| (e.kind(), e.span())).collect();
+        let right = vec![
+            (ParseErrorKind::UnsupportedLevel(Level::Three), 5..6),
+            (ParseErrorKind::UnsupportedLevel(Level::Three), 9..10),
+            (ParseErrorKind::UnsupportedLevel(Level::Three), 11..12),
+            (ParseErrorKind::UnsupportedLevel(Level::Four), 16..18),
+            (ParseErrorKind::UnsupportedLevel(Level::Four), 21..22),
+        ];
+        assert_eq!(left, right);
+
+        let left = Parser::new()
+            .max_level(Level::One)
+            .parse("/{#x}")
+            .unwrap_err();
+        assert_eq!(left.to_string(), "unsupported level 2 feature at 2..3");
+
+        let left = Parser::new()
+            .max_level(Level::Three)
+            .lenient(true)
+            .parse("{x}{y*}");
+        assert_eq!(left.unwrap().to_string(), "{x}{y*}");
+        assert!(Parser::new()
+            .max_level(Level::Three)
+            .parse("{/x,y}{?z}")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_level() {
+        let left: Vec<_> = vec!["/users", "{x}", "{+x}", "{#x}{y}", "{x,y}", "{?x}", "{x:3}"]
+            .into_iter()
+            .map(|template| UriTemplate::parse(template).level())
+            .collect();
+        let right = [1, 1, 2, 2, 3, 3, 4];
+        assert_eq!(left, right.map(|level| Level::try_from(level).unwrap()));
+        assert_eq!(Level::try_from(5), Err(5));
+    }
+
+    fn match_uri(template: &str, uri: &str) -> Result<Vec<(String, Value)>, MatchError> {
+        let uri_template = UriTemplate::parse(template);
+        let variables = uri_template.match_uri(uri)?;
+        assert_eq!(uri_template.expand(&variables), uri);
+        Ok(variables)
+    }
+
+    #[test]
+    fn test_match_uri() {
+        let left = match_uri("/users/{id}/posts{?page,limit}", "/users/42/posts?page=3");
+        let right = vec![
+            ("id".to_string(), Value::from_string("42")),
+            ("page".to_string(), Value::from_string("3")),
+        ];
+        assert_eq!(left, Ok(right));
+
+        let left = match_uri("/users/{id}/posts{?page,limit}", "/users/42/posts");
+        assert_eq!(left, Ok(vec![("id".to_string(), Value::from_string("42"))]));
+
+        let left = match_uri("/search{?q}", "/search?q=A%20%3AB");
+        assert_eq!(
+            left,
+            Ok(vec![("q".to_string(), Value::from_string("A :B"))])
+        );
+
+        let left = match_uri("{+base}index", "http://example.com/home/index");
+        let right = vec![(
+            "base".to_string(),
+            Value::from_string("http://example.com/home/"),
+        )];
+        assert_eq!(left, Ok(right));
+
+        let left = match_uri("{#x,y}", "#1024,768");
+        let right = vec![
+            ("x".to_string(), Value::from_string("1024")),
+            ("y".to_string(), Value::from_string("768")),
+        ];
+        assert_eq!(left, Ok(right));
+    }
+
+    #[test]
+    fn test_match_uri_list_and_assoc() {
+        let left = match_uri("x{/y*}", "x/A//B");
+        assert_eq!(
+            left,
+            Ok(vec![("y".to_string(), Value::from_list(["A", "", "B"]))])
+        );
+
+        let left = match_uri("x{/y*}", "x/a=A/b=/c=C");
+        let right = Value::from_assoc([("a", "A"), ("b", ""), ("c", "C")]);
+        assert_eq!(left, Ok(vec![("y".to_string(), right)]));
+
+        let left = match_uri("x{;y*}", "x;y=A;y;y=B");
+        assert_eq!(
+            left,
+            Ok(vec![("y".to_string(), Value::from_list(["A", "", "B"]))])
+        );
+
+        let left = match_uri("x{?y*,z}", "x?a=A&b=B&z=Z");
+        let right = vec![
+            ("y".to_string(), Value::from_assoc([("a", "A"), ("b", "B")])),
+            ("z".to_string(), Value::from_string("Z")),
+        ];
+        assert_eq!(left, Ok(right));
+
+        let left = match_uri("{.dom}", ".example,com");
+        assert_eq!(
+            left,
+            Ok(vec![(
+                "dom".to_string(),
+                Value::from_list(["example", "com"])
+            )])
+        );
+    }
+
+    #[test]
+    fn test_match_uri_prefix() {
+        let left = match_uri("{var:3}/{var}", "val/value");
+        assert_eq!(
+            left,
+            Ok(vec![("var".to_string(), Value::from_string("value"))])
+        );
+
+        assert_eq!(match_uri("{var:3}", "value"), Err(MatchError::NoMatch));
+        assert_eq!(
+            match_uri("{var:3}/{var}", "val/other"),
+            Err(MatchError::NoMatch)
+        );
+    }
+
+    #[test]
+    fn test_match_uri_errors() {
+        assert_eq!(
+            match_uri("/users/{id}", "/posts/42"),
+            Err(MatchError::NoMatch)
+        );
+        assert_eq!(
+            match_uri("/users/{id}", "/users/4/2"),
+            Err(MatchError::NoMatch)
+        );
+        assert_eq!(match_uri("{?x}", "?x=1&y=2"), Err(MatchError::NoMatch));
+        assert_eq!(match_uri("{x}{y}", "ab"), Err(MatchError::Ambiguous));
+    }
+
+    #[test]
+    fn test_match_uri_long() {
+        let uri_template = UriTemplate::parse("/{a}{b}{c}{d}{e}{f}/end");
+        let uri = format!("/{}", "x".repeat(5000));
+        assert_eq!(uri_template.match_uri(&uri), Err(MatchError::NoMatch));
+        let uri = format!("/{}/end", "x".repeat(5000));
+        assert_eq!(uri_template.match_uri(&uri), Err(MatchError::Ambiguous));
+
+        let uri_template = UriTemplate::parse("{/a,b}{/c,d}{/e,f}{;x}");
+        let uri = format!("{};y", "/1".repeat(2000));
+        assert_eq!(uri_template.match_uri(&uri), Err(MatchError::NoMatch));
+    }
+
+    fn regex_captures(template: &str, uri: &str) -> Option<Vec<(String, String)>> {
+        let pattern = UriTemplate::parse(template).to_regex_pattern();
+        let regex = regex::Regex::new(&pattern).unwrap();
+        let captures = regex.captures(uri)?;
+        let captures = regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_string(), captures.name(name)?.as_str().to_string())))
+            .collect();
+        Some(captures)
+    }
+
+    #[test]
+    fn test_to_regex_pattern() {
+        let left = regex_captures("/users/{id}/posts{?page,limit}", "/users/42/posts?page=3");
+        let right = vec![
+            ("id".to_string(), "42".to_string()),
+            ("page".to_string(), "3".to_string()),
+        ];
+        assert_eq!(left, Some(right));
+
+        let left = regex_captures("/users/{id}/posts{?page,limit}", "/users/4%C3%A9/posts");
+        assert_eq!(left, Some(vec![("id".to_string(), "4%C3%A9".to_string())]));
+
+        let left = regex_captures("{x.y}{;z}", "a.b;z");
+        let right = vec![("x_y".to_string(), "a.b".to_string())];
+        assert_eq!(left, Some(right));
+
+        let left = regex_captures("{+base}index", "http://example.com/home/index");
+        let right = vec![("base".to_string(), "http://example.com/home/".to_string())];
+        assert_eq!(left, Some(right));
+
+        let left = regex_captures("x{/y*}{?x}", "x/a=A/b=/c=C?x=1");
+        let right = vec![
+            ("y".to_string(), "a=A/b=/c=C".to_string()),
+            ("x".to_string(), "1".to_string()),
+        ];
+        assert_eq!(left, Some(right));
+
+        assert_eq!(regex_captures("/users/{id}", "/users/4/2"), None);
+        assert_eq!(regex_captures("/users/{id}", "/users/A B"), None);
+    }
+
+    #[test]
+    fn test_to_regex_pattern_prefix() {
+        let left = regex_captures("{var:3}", "val");
+        assert_eq!(left, Some(vec![("var".to_string(), "val".to_string())]));
+
+        let left = regex_captures("{var:2}", "%C3%A9t");
+        assert_eq!(left, Some(vec![("var".to_string(), "%C3%A9t".to_string())]));
+
+        assert_eq!(regex_captures("{var:3}", "valu"), None);
+    }
+
+    #[test]
+    fn test_to_regex_pattern_group_names() {
+        let pattern = UriTemplate::parse("{x}/{x}/{1%20}").to_regex_pattern();
+        let regex = regex::Regex::new(&pattern).unwrap();
+        let left: Vec<_> = regex.capture_names().flatten().collect();
+        assert_eq!(left, ["x", "x_2", "_1_20"]);
+    }
+
+    #[test]
+    fn test_items() {
+        let uri_template = UriTemplate::parse("/users/{id}/posts{?page,limit:3,fields*}");
+
+        let left: Vec<_> = uri_template.items().filter_map(Item::as_literal).collect();
+        assert_eq!(left, ["/users/", "/posts"]);
+
+        let left: Vec<_> = uri_template
+            .expressions()
+            .map(Expression::operator)
+            .collect();
+        assert_eq!(left, [None, Some(Operator::FormQuery)]);
+
+        let left: Vec<_> = uri_template
+            .variables()
+            .map(|v| (v.varname(), v.modifier()))
+            .collect();
+        let right = [
+            ("id", None),
+            ("page", None),
+            ("limit", Some(ModifierLevel4::Prefix(3))),
+            ("fields", Some(ModifierLevel4::Explode)),
+        ];
+        assert_eq!(left, right);
+
+        let query = uri_template
+            .expressions()
+            .find(|e| e.operator() == Some(Operator::FormQuery))
+            .unwrap();
+        let left: Vec<_> = query.varspecs().iter().map(Varspec::varname).collect();
+        assert_eq!(left, ["page", "limit", "fields"]);
+    }
+
+    #[test]
+    fn test_variable_names() {
+        let uri_template = UriTemplate::parse("{a}/{b,a}{?c}");
+        assert_eq!(uri_template.variable_names(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_variable_usages() {
+        let template = "/users/{id}{/path*}?x={x:3}{&page}{#section}";
+        let uri_template = UriTemplate::parse(template);
+        let left: Vec<_> = uri_template
+            .variable_usages()
+            .iter()
+            .map(|usage| {
+                (
+                    usage.name(),
+                    usage.operator(),
+                    usage.is_exploded(),
+                    usage.prefix(),
+                    usage.position(),
+                    &template[usage.span()],
+                )
+            })
+            .collect();
+        let right = [
+            ("id", None, false, None, Position::Path, "id"),
+            (
+                "path",
+                Some(Operator::PathSegment),
+                true,
+                None,
+                Position::Path,
+                "path*",
+            ),
+            ("x", None, false, Some(3), Position::Query, "x:3"),
+            (
+                "page",
+                Some(Operator::FormContinuation),
+                false,
+                None,
+                Position::Query,
+                "page",
+            ),
+            (
+                "section",
+                Some(Operator::Fragment),
+                false,
+                None,
+                Position::Fragment,
+                "section",
+            ),
+        ];
+        assert_eq!(left, right);
+
+        let template = "{?a,b}";
+        let left: Vec<_> = UriTemplate::parse(template)
+            .variable_usages()
+            .iter()
+            .map(|usage| usage.span())
+            .collect();
+        assert_eq!(left, [2..3, 4..5]);
+    }
+
+    #[test]
+    fn test_display() {
+        for template in [
+            "",
+            "x",
+            "/users/{id}/posts{?page,limit:3,fields*}",
+            "{+a}{#b}{.c}{/d}{;e}{?f}{&g}",
+            "{x.y,%20%21}",
+            "{!x}{x:0}{",
+        ] {
+            let uri_template = UriTemplate::parse(template);
+            let left = uri_template.to_string();
+            assert_eq!(left, template);
+            assert_eq!(UriTemplate::parse(&left), uri_template);
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        let left: UriTemplate = "x{y}".parse().unwrap();
+        assert_eq!(left, UriTemplate::parse("x{y}"));
+
+        let left = "x{!y}".parse::<UriTemplate>().unwrap_err();
+        assert_eq!(left.kind(), ParseErrorKind::UnknownOperator);
+    }
+
+    #[test]
+    fn test_expand_to() {
+        let uri_template = UriTemplate::parse("/users/{id}{?q}");
+        let variables = vec![
+            ("id".to_string(), Value::from_string("42")),
+            ("q".to_string(), Value::from_string("a b")),
+        ];
+
+        let mut left = "<a href=\"".to_string();
+        uri_template.expand_to(&variables, &mut left).unwrap();
+        assert_eq!(left, "<a href=\"/users/42?q=a%20b");
+
+        let mut left = String::new();
+        let dst: &mut dyn fmt::Write = &mut left;
+        uri_template.expand_to(&variables, dst).unwrap();
+        assert_eq!(left, "/users/42?q=a%20b");
+
+        let mut left = b"Location: ".to_vec();
+        uri_template.expand_to_io(&variables, &mut left).unwrap();
+        assert_eq!(left, b"Location: /users/42?q=a%20b");
+
+        let mut left = &mut [0; 8][..];
+        let left = uri_template
+            .expand_to_io(&variables, &mut left)
+            .unwrap_err();
+        assert_eq!(left.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_expand_scalars() {
+        let variables = vec![
+            ("i".to_string(), Value::Integer(-42)),
+            ("f".to_string(), Value::Float(0.001)),
+            ("nan".to_string(), Value::Float(f64::NAN)),
+            ("b".to_string(), Value::Boolean(true)),
+            ("u".to_string(), Value::Undefined),
+            (
+                "l".to_string(),
+                Value::List(vec![
+                    Value::Integer(1),
+                    Value::Undefined,
+                    Value::Float(1e21),
+                ]),
+            ),
+        ];
+        let left = UriTemplate::parse("{?i,f,nan,b,u,l}{/i:2}{.l*}").expand(&variables);
+        assert_eq!(
+            left,
+            "?i=-42&f=0.001&b=true&l=1,1000000000000000000000/-4.1.1000000000000000000000"
+        );
+
+        let left = UriTemplate::parse("{u}{?u}").try_expand(&variables, ExpandPolicy::RequireAll);
+        assert_eq!(left, Ok("".to_string()));
+    }
+
+    #[test]
+    fn test_expand_nested() {
+        let filter = Value::AssociativeArray(vec![
+            ("q".to_string(), Value::from_string("x")),
+            (
+                "price".to_string(),
+                Value::AssociativeArray(vec![
+                    ("min".to_string(), Value::Integer(1)),
+                    ("max".to_string(), Value::Integer(9)),
+                ]),
+            ),
+            ("tags".to_string(), Value::from_list(vec!["a", "b"])),
+        ]);
+        let uri_template = UriTemplate::parse("{?filter*}");
+
+        let variables = vec![("filter".to_string(), filter.clone())];
+        assert_eq!(uri_template.expand(&variables), "?q=x");
+
+        let variables = vec![("filter".to_string(), filter.flatten(NestingPolicy::Dotted))];
+        assert_eq!(
+            uri_template.expand(&variables),
+            "?q=x&price.min=1&price.max=9&tags.0=a&tags.1=b"
+        );
+
+        let variables = vec![(
+            "filter".to_string(),
+            filter.flatten(NestingPolicy::Brackets),
+        )];
+        assert_eq!(
+            uri_template.expand(&variables),
+            "?q=x&price%5Bmin%5D=1&price%5Bmax%5D=9&tags%5B0%5D=a&tags%5B1%5D=b"
+        );
+
+        let list = Value::List(vec![Value::from_string("a"), Value::from_list(vec!["b"])]);
+        assert_eq!(
+            list.flatten(NestingPolicy::Brackets),
+            Value::from_assoc(vec![("0", "a"), ("1[0]", "b")])
+        );
+        assert_eq!(
+            Value::from_list(vec!["a"]).flatten(NestingPolicy::Dotted),
+            Value::from_list(vec!["a"])
+        );
+    }
+
+    struct Request<'a> {
+        path: &'a str,
+        ids: &'a [u32],
+    }
+
+    impl<'a> Variables<'a, ValueRef<'a>> for Request<'a> {
+        fn get(&'a self, k: &'a str) -> Option<ValueRef<'a>> {
+            match k {
+                "path" => Some(ValueRef::String(self.path)),
+                "ids" => Some(self.ids.as_value_ref()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_expand_borrowed() {
+        let request = Request {
+            path: "a/b",
+            ids: &[1, 2],
+        };
+        let left = UriTemplate::parse("{+path}{?ids}").expand(&request);
+        assert_eq!(left, "a/b?ids=1,2");
+
+        let tags = vec!["x", "y"];
+        let mut filter = BTreeMap::new();
+        filter.insert("name", "é!");
+        let uri_template = UriTemplate::parse("{tags}{?filter*}{&name:1,n:2}");
+        let left = uri_template
+            .expander()
+            .set_ref("tags", &tags)
+            .set_ref("filter", &filter)
+            .set_ref("name", "été")
+            .set_ref("n", &12345)
+            .expand();
+        assert_eq!(left, "x,y?name=%C3%A9%21&name=%C3%A9&n=12");
+
+        let empty: Vec<&str> = Vec::new();
+        let left = uri_template.expander().set_ref("tags", &empty).expand();
+        assert_eq!(left, "");
+
+        let ids: Vec<usize> = vec![1, 2];
+        let left = UriTemplate::parse("{?ids,n,max}")
+            .expander()
+            .set_ref("ids", &ids)
+            .set_ref("n", &42u64)
+            .set_ref("max", &u64::MAX)
+            .expand();
+        assert_eq!(left, "?ids=1,2&n=42&max=18446744073709551615");
+
+        let variables: Vec<(&str, Box<str>)> = vec![("x", "a".into())];
+        assert_eq!(UriTemplate::parse("{x}").expand(&variables), "a");
+        let variables: HashMap<&str, Rc<Value>> = vec![("x", Rc::new(Value::Integer(1)))]
+            .into_iter()
+            .collect();
+        assert_eq!(UriTemplate::parse("{x}").expand(&variables), "1");
+        let variables: BTreeMap<&str, Arc<String>> =
+            vec![("x", Arc::new("b".to_string()))].into_iter().collect();
+        assert_eq!(UriTemplate::parse("{x}").expand(&variables), "b");
+    }
+
+    #[test]
+    fn test_expand_iter() {
+        let uri_template = UriTemplate::parse("{?ids,filter*}");
+        let rows = vec![None, Some(1), None, Some(2)];
+        let left = uri_template
+            .expander()
+            .set_list_iter("ids", rows)
+            .set_assoc_iter("filter", vec![("q", "x")])
+            .try_expand(ExpandPolicy::RequireAll);
+        assert_eq!(left, Ok("?ids=1,2&q=x".to_string()));
+
+        let mut expander = uri_template.expander();
+        expander.set_list_iter("ids", Vec::<&str>::new());
+        let left = expander.try_expand(ExpandPolicy::RequireAll).unwrap_err();
+        assert_eq!(left.missing(), ["ids", "filter"]);
+
+        let mut cursor = 0..3;
+        let ids = IterList::new(std::iter::from_fn(|| cursor.next()));
+        let mut expander = uri_template.expander();
+        expander.set_ref("ids", &ids);
+        assert_eq!(expander.expand(), "?ids=0,1,2");
+        assert_eq!(expander.expand(), "");
+    }
+
+    fn partial_expand(template: &str, variables: &[(&str, Value)]) -> UriTemplate {
+        let variables: Vec<_> = variables
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        UriTemplate::parse(template)
+            .partial_expand(&variables)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_partial_expand() {
+        let left = partial_expand(
+            "{/base}/items{?page,sort}",
+            &[("base", Value::from_string("v1"))],
+        );
+        assert_eq!(left.to_string(), "/v1/items{?page,sort}");
+        assert_eq!(left.items().count(), 2);
+
+        let left = partial_expand("{?a,b}", &[("a", Value::from_string("1"))]);
+        assert_eq!(left.to_string(), "?a=1{&b}");
+
+        let left = partial_expand("{?a,b,c}", &[("a", Value::List(vec![]))]);
+        assert_eq!(left.to_string(), "{?b,c}");
+
+        let left = partial_expand(
+            "{/a,b}{;c,d}{x}",
+            &[
+                ("a", Value::from_string("1")),
+                ("c", Value::from_string("")),
+                ("x", Value::from_string("y z")),
+            ],
+        );
+        assert_eq!(left.to_string(), "/1{/b};c{;d}y%20z");
+    }
+
+    #[test]
+    fn test_partial_expand_equivalent() {
+        let uri_template = UriTemplate::parse("{/base}/items{?page,sort}{&q}{#section}");
+        let variables = vec![
+            ("base".to_string(), Value::from_string("v1")),
+            ("page".to_string(), Value::from_string("2")),
+            ("sort".to_string(), Value::from_string("name")),
+            ("section".to_string(), Value::from_string("top")),
+        ];
+        let right = uri_template.expand(&variables);
+        let left = uri_template
+            .partial_expand(&variables[..2].to_vec())
+            .unwrap()
+            .expand(&variables[2..].to_vec());
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_partial_expand_errors() {
+        let variables = vec![("b".to_string(), Value::from_string("1"))];
+        let left = UriTemplate::parse("{?a,b}").partial_expand(&variables);
+        assert_eq!(
+            left,
+            Err(PartialExpandError::BoundAfterUnbound("b".to_string()))
+        );
+
+        let left = UriTemplate::parse("{b,c}").partial_expand(&variables);
+        assert_eq!(
+            left,
+            Err(PartialExpandError::UnboundAfterDefined("c".to_string()))
+        );
+        assert_eq!(
+            left.unwrap_err().to_string(),
+            "variable `c` is unbound after a defined variable of the same expression"
+        );
+    }
+
+    #[test]
+    fn test_try_expand() {
+        let uri_template = UriTemplate::parse("/users/{id}{/path*}{?page,q}#{section}");
+        let variables = vec![
+            ("path".to_string(), Value::List(vec![])),
+            ("page".to_string(), Value::from_string("2")),
+        ];
+
+        let left = uri_template.try_expand(&variables, ExpandPolicy::Lenient);
+        assert_eq!(left, Ok("/users/?page=2#".to_string()));
+
+        let left = uri_template
+            .try_expand(&variables, ExpandPolicy::RequirePath)
+            .unwrap_err();
+        assert_eq!(left.missing(), ["id", "path"]);
+        assert_eq!(left.to_string(), "undefined variables `id`, `path`");
+
+        let left = uri_template
+            .try_expand(&variables, ExpandPolicy::RequireAll)
+            .unwrap_err();
+        assert_eq!(left.missing(), ["id", "path", "q", "section"]);
+    }
+
+    #[test]
+    fn test_try_expand_expander() {
+        let uri_template = UriTemplate::parse("{x}{?y}{x}");
+        let left = uri_template
+            .expander()
+            .set_string("y", "1")
+            .try_expand(ExpandPolicy::RequireAll)
+            .unwrap_err();
+        assert_eq!(left.to_string(), "undefined variable `x`");
+
+        let left = uri_template
+            .expander()
+            .set_string("x", "")
+            .try_expand(ExpandPolicy::RequirePath);
+        assert_eq!(left, Ok("".to_string()));
+    }
+
+    #[test]
+    fn test_expander_with() {
+        let defaults = vec![("host", "example.com"), ("id", "0")];
+        let uri_template = UriTemplate::parse("{host}{/id}{?q}");
+        let left = uri_template
+            .expander_with(&defaults)
+            .set_string("id", "1")
+            .set_value("q", Value::Integer(2))
+            .expand();
+        assert_eq!(left, "example.com/1?q=2");
+
+        let left = uri_template
+            .expander_with(&defaults)
+            .try_expand(ExpandPolicy::RequireAll)
+            .unwrap_err();
+        assert_eq!(left.missing(), ["q"]);
+    }
+
     #[test]
     fn test_expand_no_operator() {
         let left = UriTemplate::parse("{x}")
@@ -0,0 +1,298 @@
+//! Warnings about templates that parse but are likely mistakes.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::encoding::invalid_literal_spans;
+use crate::item::{Item, Operator};
+use crate::position::{display_len, Position, VariableUsage};
+use crate::UriTemplate;
+
+/// A check performed by a [`Linter`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Rule {
+    /// A variable has different modifiers in different expressions, e.g. `{x}{?x*}`,
+    /// `{x:3}{x:5}` or `{x:3}{x}`.
+    ConflictingModifiers,
+    /// A query expression after the query has started, e.g. `?a=1{?x}` instead of `?a=1{&x}`.
+    QueryAfterQuery,
+    /// A query continuation without a query, e.g. `/users{&x}`.
+    ContinuationWithoutQuery,
+    /// A reserved expansion in the path after its start, e.g. `/users/{+id}`, which can inject
+    /// `/`, `?` and `#`.
+    ReservedInPath,
+    /// A variable with a prefix modifier that is exploded elsewhere, e.g. `{x:3}{/x*}`.
+    PrefixAndExplode,
+    /// A literal character that is not valid in a URI, e.g. a space, `<` or `é`, or a `%` that
+    /// does not start a pct-encoded triplet. Unlike
+    /// [`LiteralMode::Validate`](crate::LiteralMode::Validate), this also reports non-ASCII
+    /// characters, which templates allow but URIs do not.
+    InvalidLiteral,
+    /// A fragment expression followed by more of the template, e.g. `{#x}/y`.
+    FragmentNotLast,
+}
+
+/// Checks templates against a set of rules, by default all of them.
+#[derive(Clone, Debug)]
+pub struct Linter {
+    enabled: Vec<Rule>,
+}
+
+/// A likely mistake found by a [`Linter`], with the rule that found it.
+///
+/// `Display` gives the message followed by the span, e.g. `fragment expression is not at the
+/// end of the template at 13..17`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    rule: Rule,
+    message: String,
+    span: Range<usize>,
+}
+
+impl Rule {
+    /// Every rule, which is what [`Linter::new`] enables.
+    pub const ALL: [Rule; 7] = [
+        Rule::ConflictingModifiers,
+        Rule::QueryAfterQuery,
+        Rule::ContinuationWithoutQuery,
+        Rule::ReservedInPath,
+        Rule::PrefixAndExplode,
+        Rule::InvalidLiteral,
+        Rule::FragmentNotLast,
+    ];
+}
+
+impl Linter {
+    /// A linter with every rule enabled.
+    pub fn new() -> Self {
+        Linter {
+            enabled: Rule::ALL.to_vec(),
+        }
+    }
+
+    /// Checks `rule`, if it was disabled.
+    pub fn enable(&mut self, rule: Rule) -> &mut Self {
+        if !self.is_enabled(rule) {
+            self.enabled.push(rule);
+        }
+        self
+    }
+
+    /// Stops checking `rule`, e.g. [`Rule::ReservedInPath`] for templates whose first segment is
+    /// a reserved expansion on purpose.
+    pub fn disable(&mut self, rule: Rule) -> &mut Self {
+        self.enabled.retain(|r| *r != rule);
+        self
+    }
+
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        self.enabled.contains(&rule)
+    }
+
+    /// The warnings of the enabled rules for `uri_template`, in order of their spans in the
+    /// template string. An empty result means the template passed every enabled rule.
+    pub fn lint(&self, uri_template: &UriTemplate) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        lint_items(&uri_template.0, &mut warnings);
+        lint_usages(&uri_template.variable_usages(), &mut warnings);
+        warnings.retain(|warning| self.is_enabled(warning.rule));
+        warnings.sort_by_key(|warning| warning.span.start);
+        warnings
+    }
+}
+
+fn lint_items(items: &[Item], warnings: &mut Vec<Warning>) {
+    let mut position = Position::Path;
+    let mut fragment = None;
+    let mut offset = 0;
+    for (i, item) in items.iter().enumerate() {
+        let span = offset..offset + display_len(item);
+        if let Some(span) = fragment.take() {
+            let message = "fragment expression is not at the end of the template";
+            warnings.push(Warning::new(Rule::FragmentNotLast, message, span));
+        }
+        match item {
+            Item::Literal(literal) => {
+                for r in invalid_literal_spans(literal) {
+                    let message = format!("invalid literal `{}`", &literal[r.clone()]);
+                    let r = offset + r.start..offset + r.end;
+                    warnings.push(Warning::new(Rule::InvalidLiteral, message, r));
+                }
+                for (j, c) in literal.char_indices().filter(|(_, c)| !c.is_ascii()) {
+                    let message = format!("non-ASCII literal `{}` must be pct-encoded", c);
+                    let r = offset + j..offset + j + c.len_utf8();
+                    warnings.push(Warning::new(Rule::InvalidLiteral, message, r));
+                }
+                if literal.contains('#') {
+                    position = Position::Fragment;
+                } else if literal.contains('?') {
+                    position = position.max(Position::Query);
+                }
+            }
+            Item::Expression(expression) => match expression.operator {
+                Some(Operator::FormQuery) => {
+                    if position == Position::Query {
+                        let message = "query expression after the query has started, use `{&`";
+                        warnings.push(Warning::new(Rule::QueryAfterQuery, message, span.clone()));
+                    }
+                    position = position.max(Position::Query);
+                }
+                Some(Operator::FormContinuation) => {
+                    if position == Position::Path {
+                        let message = "query continuation without a query, use `{?`";
+                        let rule = Rule::ContinuationWithoutQuery;
+                        warnings.push(Warning::new(rule, message, span.clone()));
+                    }
+                    position = position.max(Position::Query);
+                }
+                Some(Operator::Reserved) if i > 0 && position == Position::Path => {
+                    let message = "reserved expansion in the path can inject `/`, `?` and `#`";
+                    warnings.push(Warning::new(Rule::ReservedInPath, message, span.clone()));
+                }
+                Some(Operator::Fragment) => {
+                    position = Position::Fragment;
+                    fragment = Some(span.clone());
+                }
+                _ => {}
+            },
+        }
+        offset = span.end;
+    }
+}
+
+fn lint_usages(usages: &[VariableUsage<'_>], warnings: &mut Vec<Warning>) {
+    for (i, usage) in usages.iter().enumerate() {
+        let previous = || usages[..i].iter().filter(|u| u.name() == usage.name());
+        // A prefix and an explode modifier are reported as `PrefixAndExplode` instead.
+        if previous().any(|u| {
+            (u.prefix(), u.is_exploded()) != (usage.prefix(), usage.is_exploded())
+                && !(u.prefix().is_some() && usage.is_exploded())
+                && !(u.is_exploded() && usage.prefix().is_some())
+        }) {
+            let message = format!(
+                "variable `{}` has different modifiers in different expressions",
+                usage.name()
+            );
+            let rule = Rule::ConflictingModifiers;
+            warnings.push(Warning::new(rule, message, usage.span()));
+        }
+        if previous().any(|u| {
+            (u.prefix().is_some() && usage.is_exploded())
+                || (u.is_exploded() && usage.prefix().is_some())
+        }) {
+            let message = format!(
+                "variable `{}` has a prefix modifier but is exploded elsewhere",
+                usage.name()
+            );
+            warnings.push(Warning::new(Rule::PrefixAndExplode, message, usage.span()));
+        }
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Warning {
+    fn new<S>(rule: Rule, message: S, span: Range<usize>) -> Self
+    where
+        S: Into<String>,
+    {
+        Warning {
+            rule,
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The byte range of the offending part of the template string.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(template: &str) -> Vec<(Rule, String)> {
+        Linter::new()
+            .lint(&UriTemplate::parse(template))
+            .iter()
+            .map(|warning| (warning.rule(), template[warning.span()].to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        assert_eq!(lint("{+base}/users/{id}{?page}{&size}{#section}"), []);
+
+        let left = lint("/users/{+id}{&x}?a=1{?y}{#z}/");
+        let right = [
+            (Rule::ReservedInPath, "{+id}".to_string()),
+            (Rule::ContinuationWithoutQuery, "{&x}".to_string()),
+            (Rule::QueryAfterQuery, "{?y}".to_string()),
+            (Rule::FragmentNotLast, "{#z}".to_string()),
+        ];
+        assert_eq!(left, right);
+
+        let left = lint("{x}{/x*}{y:3}{?y*}");
+        let right = [
+            (Rule::ConflictingModifiers, "x*".to_string()),
+            (Rule::PrefixAndExplode, "y*".to_string()),
+        ];
+        assert_eq!(left, right);
+
+        let left = lint("{x:3}{x:5}{x:3}{y:3}{/y}");
+        let right = [
+            (Rule::ConflictingModifiers, "x:5".to_string()),
+            (Rule::ConflictingModifiers, "x:3".to_string()),
+            (Rule::ConflictingModifiers, "y".to_string()),
+        ];
+        assert_eq!(left, right);
+
+        let left = lint("/a b/%41%4<é>");
+        let right = [
+            (Rule::InvalidLiteral, " ".to_string()),
+            (Rule::InvalidLiteral, "%".to_string()),
+            (Rule::InvalidLiteral, "<".to_string()),
+            (Rule::InvalidLiteral, "é".to_string()),
+            (Rule::InvalidLiteral, ">".to_string()),
+        ];
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_linter_rules() {
+        let uri_template = UriTemplate::parse("/users/{+id} {#x}/");
+        let left = Linter::new()
+            .disable(Rule::InvalidLiteral)
+            .disable(Rule::ReservedInPath)
+            .lint(&uri_template);
+        assert_eq!(left.len(), 1);
+        assert_eq!(
+            left[0].to_string(),
+            "fragment expression is not at the end of the template at 13..17"
+        );
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use crate::encoding::pct_decode;
+use crate::expand::{get_operator_table, OperatorTable};
+use crate::item::{Expression, Item, ModifierLevel4, Operator, Varspec};
+use crate::Value;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MatchError {
+    /// The URI is not an expansion of the template.
+    NoMatch,
+    /// The URI is an expansion of the template for more than one set of variables.
+    Ambiguous,
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            MatchError::NoMatch => "URI does not match the template",
+            MatchError::Ambiguous => "URI matches the template in more than one way",
+        };
+        f.write_str(s)
+    }
+}
+
+impl Error for MatchError {}
+
+#[derive(Clone)]
+struct Binding {
+    varname: String,
+    value: Value,
+    prefix: bool,
+}
+
+/// Finds the variables for which `items` expands to `uri`.
+///
+/// Every way of splitting `uri` between the expressions is tried, so that an ambiguous template
+/// such as `{x}{y}` is reported as such instead of silently picking one interpretation. Splits
+/// after which the rest of the template cannot match are skipped, see [`Matcher`].
+pub fn match_items(items: &[Item], uri: &str) -> Result<Vec<(String, Value)>, MatchError> {
+    let mut matcher = Matcher::new(items, uri);
+    matcher.match_from(0, 0, Vec::new());
+    let mut matches = matcher.matches;
+    match matches.len() {
+        0 => Err(MatchError::NoMatch),
+        1 => Ok(matches.remove(0)),
+        _ => Err(MatchError::Ambiguous),
+    }
+}
+
+/// The state of a search for the ways `items` can match `uri`, by item index and byte offset.
+///
+/// Two tables keep the search polynomial on URIs that do not match. `reachable` rules out splits
+/// after which the remaining items cannot match even if every expression could expand to any run
+/// of its expansion characters, e.g. when the final literal is missing. `dead` records states
+/// from which the search found no match while none of the remaining variables was bound, since
+/// their outcome does not depend on the bindings.
+struct Matcher<'a> {
+    items: &'a [Item],
+    uri: &'a str,
+    reachable: Vec<Vec<bool>>,
+    later_varnames: Vec<Vec<&'a str>>,
+    dead: HashSet<(usize, usize)>,
+    matches: Vec<Vec<(String, Value)>>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(items: &'a [Item], uri: &'a str) -> Self {
+        let mut later_varnames = vec![Vec::new(); items.len() + 1];
+        for (i, item) in items.iter().enumerate().rev() {
+            let mut varnames = later_varnames[i + 1].clone();
+            if let Item::Expression(expression) = item {
+                varnames.extend(expression.variable_list.iter().map(|v| v.varname.as_str()));
+            }
+            later_varnames[i] = varnames;
+        }
+        Matcher {
+            items,
+            uri,
+            reachable: reachable(items, uri),
+            later_varnames,
+            dead: HashSet::new(),
+            matches: Vec::new(),
+        }
+    }
+
+    /// Matches `items[i..]` against `uri[offset..]`, returning whether any way was found.
+    fn match_from(&mut self, i: usize, offset: usize, bindings: Vec<Binding>) -> bool {
+        if self.matches.len() > 1 || !self.reachable[i][offset] {
+            return false;
+        }
+        let later_varnames = &self.later_varnames[i];
+        let memoize = bindings
+            .iter()
+            .all(|b| !later_varnames.contains(&b.varname.as_str()));
+        if memoize && self.dead.contains(&(i, offset)) {
+            return false;
+        }
+        let found = match self.items.get(i) {
+            None => {
+                let m = bindings.into_iter().map(|b| (b.varname, b.value)).collect();
+                if !self.matches.contains(&m) {
+                    self.matches.push(m);
+                }
+                true
+            }
+            Some(Item::Literal(literal)) => {
+                self.match_from(i + 1, offset + literal.len(), bindings)
+            }
+            Some(Item::Expression(expression)) => {
+                let operator_table = get_operator_table(expression.operator);
+                let reserved = matches!(
+                    expression.operator,
+                    Some(Operator::Reserved) | Some(Operator::Fragment)
+                );
+                let uri = &self.uri[offset..];
+                let len = uri
+                    .find(|c| !is_expansion_char(&operator_table, c))
+                    .unwrap_or(uri.len());
+                let mut found = false;
+                for end in (0..=len).rev() {
+                    if !self.reachable[i + 1][offset + end] {
+                        continue;
+                    }
+                    let captures =
+                        match_expression(expression, &operator_table, reserved, &uri[..end]);
+                    if let Some(captures) = captures {
+                        let mut bindings = bindings.clone();
+                        if captures.into_iter().all(|c| bind(&mut bindings, c)) {
+                            found |= self.match_from(i + 1, offset + end, bindings);
+                        }
+                    }
+                }
+                found
+            }
+        };
+        if memoize && !found {
+            self.dead.insert((i, offset));
+        }
+        found
+    }
+}
+
+/// `reachable(items, uri)[i][offset]` is whether `items[i..]` can match `uri[offset..]`, if
+/// every expression can expand to any run of its expansion characters.
+fn reachable(items: &[Item], uri: &str) -> Vec<Vec<bool>> {
+    let bytes = uri.as_bytes();
+    let n = bytes.len();
+    let mut table = vec![vec![false; n + 1]; items.len() + 1];
+    table[items.len()][n] = true;
+    for (i, item) in items.iter().enumerate().rev() {
+        let (head, tail) = table.split_at_mut(i + 1);
+        let (row, next) = (&mut head[i], &tail[0]);
+        match item {
+            Item::Literal(literal) => {
+                let literal = literal.as_bytes();
+                for offset in 0..=n {
+                    row[offset] =
+                        bytes[offset..].starts_with(literal) && next[offset + literal.len()];
+                }
+            }
+            Item::Expression(expression) => {
+                // Expansion characters are ASCII, so the run ends at any other byte.
+                let operator_table = get_operator_table(expression.operator);
+                let mut run_end = n;
+                let mut next_reachable = None;
+                for offset in (0..=n).rev() {
+                    if offset < n
+                        && !(bytes[offset].is_ascii()
+                            && is_expansion_char(&operator_table, char::from(bytes[offset])))
+                    {
+                        run_end = offset;
+                    }
+                    if next[offset] {
+                        next_reachable = Some(offset);
+                    }
+                    row[offset] = next_reachable.is_some_and(|end| end <= run_end);
+                }
+            }
+        }
+    }
+    table
+}
+
+fn is_expansion_char(operator_table: &OperatorTable, c: char) -> bool {
+    '%' == c
+        || ',' == c
+        || '=' == c
+        || (operator_table.is_allowed)(c)
+        || operator_table.first.contains(c)
+        || operator_table.sep.contains(c)
+}
+
+fn bind(bindings: &mut Vec<Binding>, binding: Binding) -> bool {
+    match bindings.iter_mut().find(|b| b.varname == binding.varname) {
+        None => {
+            bindings.push(binding);
+            true
+        }
+        Some(old) => match (&old.value, &binding.value) {
+            (x, y) if x == y => true,
+            (Value::String(x), Value::String(y)) if binding.prefix && x.starts_with(y.as_str()) => {
+                true
+            }
+            (Value::String(x), Value::String(y)) if old.prefix && y.starts_with(x.as_str()) => {
+                *old = binding;
+                true
+            }
+            _ => false,
+        },
+    }
+}
+
+fn match_expression(
+    expression: &Expression,
+    operator_table: &OperatorTable,
+    reserved: bool,
+    s: &str,
+) -> Option<Vec<Binding>> {
+    if s.is_empty() {
+        return Some(Vec::new());
+    }
+    let s = s.strip_prefix(operator_table.first)?;
+    let varspecs = &expression.variable_list;
+    let bindings = if operator_table.named {
+        match_named(varspecs, operator_table, s)?
+    } else {
+        match_unnamed(varspecs, operator_table, reserved, s)?
+    };
+    for binding in &bindings {
+        let varspec = varspecs.iter().find(|v| v.varname == binding.varname)?;
+        if let Some(ModifierLevel4::Prefix(size)) = varspec.modifier_level4 {
+            match &binding.value {
+                Value::String(value) if value.chars().count() <= size => {}
+                _ => return None,
+            }
+        }
+    }
+    Some(bindings)
+}
+
+fn match_named(
+    varspecs: &[Varspec],
+    operator_table: &OperatorTable,
+    s: &str,
+) -> Option<Vec<Binding>> {
+    let parts: Vec<(&str, &str)> = s
+        .split(operator_table.sep)
+        .map(|part| part.split_once('=').unwrap_or((part, "")))
+        .collect();
+    let mut parts = &parts[..];
+    let mut bindings = Vec::new();
+    for (i, varspec) in varspecs.iter().enumerate() {
+        if let Some(ModifierLevel4::Explode) = varspec.modifier_level4 {
+            let later = &varspecs[i + 1..];
+            let n = parts
+                .iter()
+                .take_while(|(k, _)| later.iter().all(|v| v.varname != *k))
+                .count();
+            if n > 0 {
+                let (exploded, rest) = parts.split_at(n);
+                let value = if exploded.iter().all(|(k, _)| *k == varspec.varname) {
+                    let values: Vec<&str> = exploded.iter().map(|(_, v)| *v).collect();
+                    list_or_string(&values)?
+                } else {
+                    assoc(exploded.iter().copied())?
+                };
+                bindings.push(Binding::new(varspec, value));
+                parts = rest;
+            }
+        } else if let Some(((k, v), rest)) = parts.split_first() {
+            if *k == varspec.varname {
+                let value = unexploded_value(v, false)?;
+                bindings.push(Binding::new(varspec, value));
+                parts = rest;
+            }
+        }
+    }
+    if parts.is_empty() {
+        Some(bindings)
+    } else {
+        None
+    }
+}
+
+fn match_unnamed(
+    varspecs: &[Varspec],
+    operator_table: &OperatorTable,
+    reserved: bool,
+    s: &str,
+) -> Option<Vec<Binding>> {
+    let explode = varspecs
+        .iter()
+        .position(|v| v.modifier_level4 == Some(ModifierLevel4::Explode));
+    if varspecs.len() == 1 && explode.is_none() {
+        if !reserved && "," != operator_table.sep && s.contains(operator_table.sep) {
+            return None;
+        }
+        let value = unexploded_value(s, reserved)?;
+        return Some(vec![Binding::new(&varspecs[0], value)]);
+    }
+    let parts: Vec<&str> = s.split(operator_table.sep).collect();
+    let mut bindings = Vec::new();
+    match explode {
+        None => {
+            if parts.len() > varspecs.len() {
+                return None;
+            }
+            for (varspec, part) in varspecs.iter().zip(parts) {
+                let value = unexploded_value(part, reserved)?;
+                bindings.push(Binding::new(varspec, value));
+            }
+        }
+        Some(explode) => {
+            if parts.len() < varspecs.len() {
+                return None;
+            }
+            let n = parts.len() - (varspecs.len() - 1);
+            let (before, rest) = parts.split_at(explode);
+            let (exploded, after) = rest.split_at(n);
+            let unexploded = before.iter().chain(after);
+            for (varspec, part) in varspecs[..explode]
+                .iter()
+                .chain(&varspecs[explode + 1..])
+                .zip(unexploded)
+            {
+                let value = unexploded_value(part, reserved)?;
+                bindings.push(Binding::new(varspec, value));
+            }
+            let value = if exploded.iter().all(|part| part.contains('=')) {
+                assoc(exploded.iter().map(|part| part.split_once('=').unwrap()))?
+            } else {
+                list_or_string(exploded)?
+            };
+            bindings.insert(explode, Binding::new(&varspecs[explode], value));
+        }
+    }
+    Some(bindings)
+}
+
+/// Decodes an unexploded value, where a raw comma can only separate list items unless the
+/// operator allows reserved characters.
+fn unexploded_value(s: &str, reserved: bool) -> Option<Value> {
+    if !reserved && s.contains(',') {
+        s.split(',')
+            .map(pct_decode)
+            .collect::<Option<Vec<_>>>()
+            .map(Value::from_list)
+    } else {
+        pct_decode(s).map(Value::String)
+    }
+}
+
+fn list_or_string(values: &[&str]) -> Option<Value> {
+    let mut values = values
+        .iter()
+        .map(|v| pct_decode(v))
+        .collect::<Option<Vec<_>>>()?;
+    if values.len() == 1 {
+        values.pop().map(Value::String)
+    } else {
+        Some(Value::from_list(values))
+    }
+}
+
+fn assoc<'a, I>(iter: I) -> Option<Value>
+where
+    I: Iterator<Item = (&'a str, &'a str)>,
+{
+    iter.map(|(k, v)| Some((pct_decode(k)?, pct_decode(v)?)))
+        .collect::<Option<Vec<_>>>()
+        .map(Value::from_assoc)
+}
+
+impl Binding {
+    fn new(varspec: &Varspec, value: Value) -> Self {
+        Binding {
+            varname: varspec.varname.clone(),
+            value,
+            prefix: matches!(varspec.modifier_level4, Some(ModifierLevel4::Prefix(_))),
+        }
+    }
+}
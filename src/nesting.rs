@@ -0,0 +1,63 @@
+use crate::Value;
+
+/// How [`Value::flatten`](crate::Value::flatten) names the items of nested lists and associative
+/// arrays.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NestingPolicy {
+    /// `a.b`
+    Dotted,
+    /// `a[b]`
+    Brackets,
+}
+
+pub fn flatten(value: &Value, policy: NestingPolicy) -> Value {
+    match value {
+        Value::List(items) if items.iter().any(is_nested) => {
+            let mut dst = Vec::new();
+            for (i, v) in items.iter().enumerate() {
+                push_flattened(&mut dst, policy, i.to_string(), v);
+            }
+            Value::AssociativeArray(dst)
+        }
+        Value::AssociativeArray(entries) if entries.iter().any(|(_, v)| is_nested(v)) => {
+            let mut dst = Vec::new();
+            for (k, v) in entries {
+                push_flattened(&mut dst, policy, k.clone(), v);
+            }
+            Value::AssociativeArray(dst)
+        }
+        value => value.clone(),
+    }
+}
+
+fn push_flattened(
+    dst: &mut Vec<(String, Value)>,
+    policy: NestingPolicy,
+    key: String,
+    value: &Value,
+) {
+    match value {
+        Value::List(items) => {
+            for (i, v) in items.iter().enumerate() {
+                push_flattened(dst, policy, join(policy, &key, &i.to_string()), v);
+            }
+        }
+        Value::AssociativeArray(entries) => {
+            for (k, v) in entries {
+                push_flattened(dst, policy, join(policy, &key, k), v);
+            }
+        }
+        value => dst.push((key, value.clone())),
+    }
+}
+
+fn join(policy: NestingPolicy, key: &str, segment: &str) -> String {
+    match policy {
+        NestingPolicy::Dotted => format!("{}.{}", key, segment),
+        NestingPolicy::Brackets => format!("{}[{}]", key, segment),
+    }
+}
+
+fn is_nested(value: &Value) -> bool {
+    matches!(value, Value::List(_) | Value::AssociativeArray(_))
+}
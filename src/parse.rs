@@ -1,58 +1,244 @@
-use std::str::Chars;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use std::str::CharIndices;
 
-use crate::encoding::{is_alpha, is_digit, is_hexdig};
-use crate::item::{Expression, Item, ModifierLevel4, Operator, Varspec};
+use crate::encoding::{invalid_literal_spans, is_alpha, is_digit, is_hexdig, push_literal_encoded};
+use crate::item::{Expression, Item, Level, ModifierLevel4, Operator, Varspec};
+use crate::position::display_len;
 
-pub fn parse_template(mut template: &str) -> Vec<Item> {
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    span: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    EmptyExpression,
+    UnterminatedExpression,
+    UnknownOperator,
+    InvalidVarname,
+    InvalidPrefix,
+    MisplacedExplode,
+    InvalidPctEncoded,
+    InvalidLiteral,
+    /// A feature of the given level, above the maximum level of the parser.
+    UnsupportedLevel(Level),
+}
+
+/// How [`Parser`](crate::Parser) treats literal text, see RFC 6570 section 2.1.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LiteralMode {
+    /// Literals are kept as written and expanded unchanged.
+    #[default]
+    Verbatim,
+    /// Characters that are not allowed in literals, e.g. spaces, quotes and `<`, are errors of
+    /// kind [`ParseErrorKind::InvalidLiteral`], and so is a `%` that does not start a
+    /// pct-encoded triplet.
+    Validate,
+    /// Characters that are not unreserved or reserved, including non-ASCII characters, are
+    /// pct-encoded as RFC 6570 section 3.1 prescribes, keeping existing pct-encoded triplets.
+    ///
+    /// The literals are encoded while parsing, so the template is normalized: its `Display`
+    /// form, its items and the spans of its variable usages refer to the encoded text rather
+    /// than the input, e.g. `/a b/{x}` parses to `/a%20b/{x}`.
+    Encode,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, span: Range<usize>) -> Self {
+        ParseError { kind, span }
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// The byte range of the offending input within the template.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Renders this error for `template` like [`ParseErrors`] does, without the `error: ` prefix.
+    pub fn render(&self, template: &str) -> String {
+        let column = template[..self.span.start].chars().count();
+        let width = template[self.span()].chars().count().max(1);
+        format!(
+            "{}\n  |\n  | {}\n  | {}{}",
+            self.kind,
+            template,
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.kind, self.span.start, self.span.end)
+    }
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ParseErrorKind::EmptyExpression => "empty expression",
+            ParseErrorKind::UnterminatedExpression => "unterminated expression",
+            ParseErrorKind::UnknownOperator => "unknown operator",
+            ParseErrorKind::InvalidVarname => "invalid variable name",
+            ParseErrorKind::InvalidPrefix => "invalid prefix length",
+            ParseErrorKind::MisplacedExplode => "misplaced explode modifier",
+            ParseErrorKind::InvalidPctEncoded => "invalid percent-encoding",
+            ParseErrorKind::InvalidLiteral => "invalid literal character",
+            ParseErrorKind::UnsupportedLevel(level) => {
+                return write!(f, "unsupported level {} feature", level);
+            }
+        };
+        f.write_str(s)
+    }
+}
+
+/// Every error found in a template, together with the template text they refer to.
+///
+/// The `Display` implementation renders each error rustc-style, with carets under the offending
+/// characters:
+///
+/// ```text
+/// error: unknown operator
+///   |
+///   | /users/{!id}
+///   |         ^
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseErrors {
+    template: String,
+    errors: Vec<ParseError>,
+}
+
+impl ParseErrors {
+    pub(crate) fn new(template: &str, errors: Vec<ParseError>) -> Self {
+        ParseErrors {
+            template: template.to_string(),
+            errors,
+        }
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+}
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n\n")?;
+            }
+            write!(f, "error: {}", error.render(&self.template))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseErrors {}
+
+/// Parses `template` into items, collecting every error instead of stopping at the first.
+///
+/// Malformed expressions are kept as literals so that the returned items are always usable in
+/// lenient mode.
+pub fn parse_template(
+    template: &str,
+    literals: LiteralMode,
+    max_level: Level,
+) -> (Vec<Item>, Vec<ParseError>) {
     let mut items = Vec::new();
-    while !template.is_empty() {
-        match template.split_once('{') {
+    let mut errors = Vec::new();
+    let mut offset = 0;
+    while offset < template.len() {
+        let remainder = &template[offset..];
+        match remainder.find('{') {
             None => {
-                let item = parse_literal(template);
+                let item = parse_literal(remainder, offset, literals, &mut errors);
                 items.push(item);
                 break;
             }
-            Some((literal, remainder)) => {
-                if !literal.is_empty() {
-                    let item = parse_literal(literal);
+            Some(i) => {
+                if i > 0 {
+                    let item = parse_literal(&remainder[..i], offset, literals, &mut errors);
                     items.push(item);
                 }
-                match remainder.split_once('}') {
+                let start = offset + i;
+                match template[start..].find('}') {
                     None => {
-                        let mut s = String::new();
-                        s.push('{');
-                        s.push_str(remainder);
-                        let item = Item::Literal(s);
-                        items.push(item);
+                        let kind = ParseErrorKind::UnterminatedExpression;
+                        errors.push(ParseError::new(kind, start..template.len()));
+                        items.push(Item::Literal(template[start..].to_string()));
                         break;
                     }
-                    Some((expression, remainder)) => {
-                        let item = parse_expression(expression).unwrap_or_else(|_| {
-                            let mut s = String::new();
-                            s.push('{');
-                            s.push_str(expression);
-                            s.push('}');
-                            Item::Literal(s)
-                        });
-                        items.push(item);
-                        template = remainder;
+                    Some(j) => {
+                        let end = start + j + 1;
+                        let item = parse_expression(&template[start + 1..end - 1], start + 1)
+                            .and_then(|item| check_level(item, start + 1, max_level));
+                        match item {
+                            Ok(item) => items.push(item),
+                            Err(mut e) => {
+                                errors.append(&mut e);
+                                items.push(Item::Literal(template[start..end].to_string()));
+                            }
+                        }
+                        offset = end;
                     }
                 }
             }
         }
     }
-    items
+    (items, errors)
 }
 
-fn parse_literal(s: &str) -> Item {
-    Item::Literal(s.to_string())
+/// Parses literal text at `offset`. Malformed expressions that are kept as literals are not
+/// parsed with this, so that they are neither reported twice nor encoded.
+fn parse_literal(
+    s: &str,
+    offset: usize,
+    literals: LiteralMode,
+    errors: &mut Vec<ParseError>,
+) -> Item {
+    match literals {
+        LiteralMode::Verbatim => Item::Literal(s.to_string()),
+        LiteralMode::Validate => {
+            for span in invalid_literal_spans(s) {
+                let kind = ParseErrorKind::InvalidLiteral;
+                errors.push(ParseError::new(
+                    kind,
+                    offset + span.start..offset + span.end,
+                ));
+            }
+            Item::Literal(s.to_string())
+        }
+        LiteralMode::Encode => {
+            let mut literal = String::new();
+            push_literal_encoded(&mut literal, s).expect("writing to a String never fails");
+            Item::Literal(literal)
+        }
+    }
 }
 
-fn parse_expression(mut s: &str) -> Result<Item, ()> {
+fn parse_expression(mut s: &str, mut offset: usize) -> Result<Item, Vec<ParseError>> {
     let mut chars = s.chars();
     match chars.next() {
-        None => Err(()),
+        None => {
+            let kind = ParseErrorKind::EmptyExpression;
+            Err(vec![ParseError::new(kind, offset - 1..offset + 1)])
+        }
         Some(operator) => {
+            let mut errors = Vec::new();
             let operator = match operator {
                 '+' => Some(Operator::Reserved),
                 '#' => Some(Operator::Fragment),
@@ -61,35 +247,95 @@ fn parse_expression(mut s: &str) -> Result<Item, ()> {
                 ';' => Some(Operator::PathParameter),
                 '?' => Some(Operator::FormQuery),
                 '&' => Some(Operator::FormContinuation),
+                '=' | ',' | '!' | '@' | '|' => {
+                    let kind = ParseErrorKind::UnknownOperator;
+                    errors.push(ParseError::new(kind, offset..offset + 1));
+                    None
+                }
                 _ => None,
             };
-            if operator.is_some() {
+            if operator.is_some() || !errors.is_empty() {
                 s = chars.as_str();
+                offset += 1;
             }
-            let variable_list = parse_variable_list(s)?;
-            let expression = Expression {
-                operator,
-                variable_list,
-            };
-            let item = Item::Expression(expression);
-            Ok(item)
+            match parse_variable_list(s, offset) {
+                Ok(variable_list) if errors.is_empty() => {
+                    let expression = Expression {
+                        operator,
+                        variable_list,
+                    };
+                    let item = Item::Expression(expression);
+                    Ok(item)
+                }
+                Ok(_) => Err(errors),
+                Err(mut e) => {
+                    errors.append(&mut e);
+                    Err(errors)
+                }
+            }
+        }
+    }
+}
+
+/// Rejects the operator, variable list or modifiers of an expression at `offset` that are above
+/// `max_level`.
+fn check_level(item: Item, mut offset: usize, max_level: Level) -> Result<Item, Vec<ParseError>> {
+    let expression = match &item {
+        Item::Expression(expression) if expression.level() > max_level => expression,
+        _ => return Ok(item),
+    };
+    let mut errors = Vec::new();
+    if let Some(operator) = expression.operator {
+        if operator.level() > max_level {
+            let kind = ParseErrorKind::UnsupportedLevel(operator.level());
+            errors.push(ParseError::new(kind, offset..offset + 1));
         }
+        offset += 1;
     }
+    for (i, varspec) in expression.variable_list.iter().enumerate() {
+        if i == 1 && max_level < Level::Three {
+            let kind = ParseErrorKind::UnsupportedLevel(Level::Three);
+            errors.push(ParseError::new(kind, offset - 1..offset));
+        }
+        let end = offset + display_len(varspec);
+        if varspec.modifier_level4.is_some() && max_level < Level::Four {
+            let kind = ParseErrorKind::UnsupportedLevel(Level::Four);
+            errors.push(ParseError::new(kind, offset + varspec.varname.len()..end));
+        }
+        offset = end + 1;
+    }
+    Err(errors)
 }
 
-fn parse_variable_list(s: &str) -> Result<Vec<Varspec>, ()> {
-    s.split(',').map(parse_varspec).collect()
+fn parse_variable_list(s: &str, mut offset: usize) -> Result<Vec<Varspec>, Vec<ParseError>> {
+    let mut variable_list = Vec::new();
+    let mut errors = Vec::new();
+    for varspec in s.split(',') {
+        match parse_varspec(varspec, offset) {
+            Ok(varspec) => variable_list.push(varspec),
+            Err(error) => errors.push(error),
+        }
+        offset += varspec.len() + 1;
+    }
+    if errors.is_empty() {
+        Ok(variable_list)
+    } else {
+        Err(errors)
+    }
 }
 
-fn parse_varspec(s: &str) -> Result<Varspec, ()> {
+fn parse_varspec(s: &str, offset: usize) -> Result<Varspec, ParseError> {
     if s.is_empty() {
-        Err(())
+        Err(ParseError::new(
+            ParseErrorKind::InvalidVarname,
+            offset..offset,
+        ))
     } else {
         let asterisk = s.find('*');
         let colon = s.find(':');
         match (asterisk, colon) {
             (None, None) => {
-                let varname = parse_varname(s)?;
+                let varname = parse_varname(s, offset)?;
                 let varspec = Varspec {
                     varname,
                     modifier_level4: None,
@@ -99,14 +345,18 @@ fn parse_varspec(s: &str) -> Result<Varspec, ()> {
             (None, Some(colon)) => {
                 let varname = &s[..colon];
                 let size = &s[colon + 1..];
-                let varname = parse_varname(varname)?;
+                let varname = parse_varname(varname, offset)?;
+                let invalid_prefix = || {
+                    let kind = ParseErrorKind::InvalidPrefix;
+                    ParseError::new(kind, offset + colon..offset + s.len())
+                };
                 let mut chars = size.chars();
                 match chars.next() {
-                    None | Some('0') => Err(()),
+                    None | Some('0') => Err(invalid_prefix()),
                     _ => {
-                        let size = size.parse().map_err(|_| ())?;
+                        let size = size.parse().map_err(|_| invalid_prefix())?;
                         if size >= 10000 {
-                            Err(())
+                            Err(invalid_prefix())
                         } else {
                             let varspec = Varspec {
                                 varname,
@@ -117,32 +367,36 @@ fn parse_varspec(s: &str) -> Result<Varspec, ()> {
                     }
                 }
             }
-            (Some(asterisk), None) => {
-                if asterisk != s.len() - 1 {
-                    Err(())
-                } else {
-                    let varname = parse_varname(&s[..asterisk])?;
-                    let varspec = Varspec {
-                        varname,
-                        modifier_level4: Some(ModifierLevel4::Explode),
-                    };
-                    Ok(varspec)
-                }
+            (Some(asterisk), None) if asterisk == s.len() - 1 => {
+                let varname = parse_varname(&s[..asterisk], offset)?;
+                let varspec = Varspec {
+                    varname,
+                    modifier_level4: Some(ModifierLevel4::Explode),
+                };
+                Ok(varspec)
+            }
+            (Some(asterisk), _) => {
+                let start = offset + asterisk;
+                let kind = ParseErrorKind::MisplacedExplode;
+                Err(ParseError::new(kind, start..start + 1))
             }
-            _ => Err(()),
         }
     }
 }
 
-fn parse_varname(s: &str) -> Result<String, ()> {
-    let mut chars = s.chars();
+fn parse_varname(s: &str, offset: usize) -> Result<String, ParseError> {
+    let mut chars = s.char_indices();
     match chars.next() {
-        Some('%') => {
-            require_pct_encoded(&mut chars)?;
+        Some((i, '%')) => {
+            require_pct_encoded(&mut chars, offset, i)?;
         }
-        Some(c) if is_varchar(c) => {}
-        _ => {
-            return Err(());
+        Some((_, c)) if is_varchar(c) => {}
+        Some((i, c)) => {
+            return Err(invalid_varname(offset + i, c));
+        }
+        None => {
+            let kind = ParseErrorKind::InvalidVarname;
+            return Err(ParseError::new(kind, offset..offset));
         }
     }
     loop {
@@ -150,30 +404,41 @@ fn parse_varname(s: &str) -> Result<String, ()> {
             None => {
                 break;
             }
-            Some('%') => {
-                require_pct_encoded(&mut chars)?;
+            Some((i, '%')) => {
+                require_pct_encoded(&mut chars, offset, i)?;
             }
-            Some(c) if is_varchar(c) || '.' == c => {}
-            _ => {
-                return Err(());
+            Some((_, c)) if is_varchar(c) || '.' == c => {}
+            Some((i, c)) => {
+                return Err(invalid_varname(offset + i, c));
             }
         }
     }
     Ok(s.to_string())
 }
 
-fn require_pct_encoded(chars: &mut Chars) -> Result<(), ()> {
-    if is_pct_encoded(chars) {
-        Ok(())
-    } else {
-        Err(())
-    }
+fn invalid_varname(start: usize, c: char) -> ParseError {
+    let kind = ParseErrorKind::InvalidVarname;
+    ParseError::new(kind, start..start + c.len_utf8())
 }
 
-fn is_pct_encoded(chars: &mut Chars) -> bool {
+fn require_pct_encoded(
+    chars: &mut CharIndices,
+    offset: usize,
+    start: usize,
+) -> Result<(), ParseError> {
     let x = chars.next();
     let y = chars.next();
-    matches!((x, y), (Some(x), Some(y)) if is_hexdig(x) && is_hexdig(y))
+    match (x, y) {
+        (Some((_, x)), Some((_, y))) if is_hexdig(x) && is_hexdig(y) => Ok(()),
+        _ => {
+            let end = match y.or(x) {
+                Some((i, c)) => i + c.len_utf8(),
+                None => start + 1,
+            };
+            let kind = ParseErrorKind::InvalidPctEncoded;
+            Err(ParseError::new(kind, offset + start..offset + end))
+        }
+    }
 }
 
 fn is_varchar(c: char) -> bool {
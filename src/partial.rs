@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::expand::expand_varspecs;
+use crate::item::{Expression, Item, Operator};
+use crate::value_ref::{is_defined, AsValueRef};
+use crate::Variables;
+
+/// Why a template cannot be partially expanded into an equivalent template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PartialExpandError {
+    /// The variable is defined but follows an unbound variable in the same expression, e.g. `y`
+    /// in `{?x,y}` when only `y` is bound.
+    BoundAfterUnbound(String),
+    /// The variable is unbound but follows a defined variable in an expression whose separator
+    /// cannot start a new expression, e.g. `y` in `{x,y}` when only `x` is bound.
+    UnboundAfterDefined(String),
+}
+
+impl fmt::Display for PartialExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartialExpandError::BoundAfterUnbound(varname) => write!(
+                f,
+                "variable `{}` is bound after an unbound variable of the same expression",
+                varname
+            ),
+            PartialExpandError::UnboundAfterDefined(varname) => write!(
+                f,
+                "variable `{}` is unbound after a defined variable of the same expression",
+                varname
+            ),
+        }
+    }
+}
+
+impl Error for PartialExpandError {}
+
+/// Expands the variables that are bound in `variables` and keeps the others as expressions.
+///
+/// The leading bound variables of an expression become a literal and the rest stay in an
+/// expression, which continues with the separator instead of the `first` prefix when something
+/// was expanded, e.g. `{?x,y}` becomes `?x=1{&y}`.
+pub fn partial_expand_items<'a, V, B>(
+    items: &'a [Item],
+    variables: &'a V,
+) -> Result<Vec<Item>, PartialExpandError>
+where
+    V: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
+{
+    let mut dst = Vec::new();
+    for item in items {
+        match item {
+            Item::Literal(literal) => push_literal(&mut dst, literal),
+            Item::Expression(expression) => {
+                partial_expand_expression(&mut dst, variables, expression)?
+            }
+        }
+    }
+    Ok(dst)
+}
+
+fn partial_expand_expression<'a, V, B>(
+    dst: &mut Vec<Item>,
+    variables: &'a V,
+    expression: &'a Expression,
+) -> Result<(), PartialExpandError>
+where
+    V: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
+{
+    let varspecs = &expression.variable_list;
+    let i = varspecs
+        .iter()
+        .position(|varspec| variables.get(&varspec.varname).is_none())
+        .unwrap_or(varspecs.len());
+    let mut unbound = Vec::new();
+    for varspec in &varspecs[i..] {
+        match variables.get(&varspec.varname) {
+            None => unbound.push(varspec.clone()),
+            Some(value) if is_defined(value.as_value_ref()) => {
+                return Err(PartialExpandError::BoundAfterUnbound(
+                    varspec.varname.clone(),
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mut literal = String::new();
+    let defined = expand_varspecs(variables, &mut literal, expression.operator, &varspecs[..i])
+        .expect("writing to a String never fails");
+    push_literal(dst, &literal);
+    if unbound.is_empty() {
+        return Ok(());
+    }
+    let operator = if defined {
+        match expression.operator {
+            Some(Operator::FormQuery) => Some(Operator::FormContinuation),
+            operator @ Some(Operator::FormContinuation)
+            | operator @ Some(Operator::Label)
+            | operator @ Some(Operator::PathSegment)
+            | operator @ Some(Operator::PathParameter) => operator,
+            None | Some(Operator::Reserved) | Some(Operator::Fragment) => {
+                return Err(PartialExpandError::UnboundAfterDefined(
+                    unbound[0].varname.clone(),
+                ));
+            }
+        }
+    } else {
+        expression.operator
+    };
+    dst.push(Item::Expression(Expression::new(operator, unbound)));
+    Ok(())
+}
+
+/// Appends `literal`, merging it with a preceding literal.
+fn push_literal(dst: &mut Vec<Item>, literal: &str) {
+    if literal.is_empty() {
+        return;
+    }
+    match dst.last_mut() {
+        Some(Item::Literal(last)) => last.push_str(literal),
+        _ => dst.push(Item::Literal(literal.to_string())),
+    }
+}
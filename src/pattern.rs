@@ -0,0 +1,143 @@
+use crate::encoding::IsAllowed;
+use crate::expand::{get_operator_table, OperatorTable};
+use crate::item::{Expression, Item, ModifierLevel4, Varspec};
+
+/// A percent-encoded character: a lead byte followed by up to three UTF-8 continuation bytes.
+const PCT_ENCODED: &str = "%[0-9A-Fa-f]{2}(?:%[89ABab][0-9A-Fa-f]){0,3}";
+
+pub fn to_regex_pattern(items: &[Item]) -> String {
+    let mut dst = String::new();
+    let mut names = Vec::new();
+    dst.push('^');
+    for item in items {
+        match item {
+            Item::Literal(literal) => push_escaped(&mut dst, literal),
+            Item::Expression(expression) => push_expression(&mut dst, &mut names, expression),
+        }
+    }
+    dst.push('$');
+    dst
+}
+
+fn push_expression(dst: &mut String, names: &mut Vec<String>, expression: &Expression) {
+    let operator_table = get_operator_table(expression.operator);
+    let single = expression.variable_list.len() == 1;
+    dst.push_str("(?:");
+    push_escaped(dst, operator_table.first);
+    for (i, varspec) in expression.variable_list.iter().enumerate() {
+        if !single {
+            dst.push_str("(?:");
+        }
+        if i > 0 {
+            dst.push_str("(?:");
+            push_escaped(dst, operator_table.sep);
+            dst.push_str(")?");
+        }
+        push_varspec(dst, names, &operator_table, varspec);
+        if !single {
+            dst.push_str(")?");
+        }
+    }
+    dst.push_str(")?");
+}
+
+fn push_varspec(
+    dst: &mut String,
+    names: &mut Vec<String>,
+    operator_table: &OperatorTable,
+    varspec: &Varspec,
+) {
+    let name = group_name(names, &varspec.varname);
+    let c = char_pattern(operator_table.is_allowed);
+    match varspec.modifier_level4 {
+        Some(ModifierLevel4::Explode) => {
+            let item = if operator_table.named {
+                format!("{}+(?:={}*)?", c, c)
+            } else {
+                format!("(?:{}|=)*", c)
+            };
+            dst.push_str(&format!("(?P<{}>{}(?:", name, item));
+            push_escaped(dst, operator_table.sep);
+            dst.push_str(&format!("{})*)", item));
+        }
+        modifier_level4 => {
+            let value = match modifier_level4 {
+                Some(ModifierLevel4::Prefix(size)) => format!("{}{{0,{}}}", c, size),
+                _ => format!("(?:{}|,)*", c),
+            };
+            if operator_table.named {
+                push_escaped(dst, &varspec.varname);
+                if operator_table.ifemp.is_empty() {
+                    dst.push_str(&format!("(?:=(?P<{}>{}))?", name, value));
+                } else {
+                    dst.push_str(&format!("=(?P<{}>{})", name, value));
+                }
+            } else {
+                dst.push_str(&format!("(?P<{}>{})", name, value));
+            }
+        }
+    }
+}
+
+/// Derives a capture group name from `varname`, which may contain characters such as `.` and `%`
+/// that are not allowed in group names, and makes it unique among `names`.
+fn group_name(names: &mut Vec<String>, varname: &str) -> String {
+    let mut name: String = varname
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    let base = name.clone();
+    let mut n = 1;
+    while names.contains(&name) {
+        n += 1;
+        name = format!("{}_{}", base, n);
+    }
+    names.push(name.clone());
+    name
+}
+
+fn char_pattern(is_allowed: IsAllowed) -> String {
+    let mut dst = String::new();
+    dst.push_str("(?:[");
+    for c in (0x21..0x7F).map(char::from).filter(|&c| is_allowed(c)) {
+        if matches!(c, '\\' | '[' | ']' | '^' | '-' | '&' | '~') {
+            dst.push('\\');
+        }
+        dst.push(c);
+    }
+    dst.push_str("]|");
+    dst.push_str(PCT_ENCODED);
+    dst.push(')');
+    dst
+}
+
+fn push_escaped(dst: &mut String, s: &str) {
+    for c in s.chars() {
+        if matches!(
+            c,
+            '\\' | '.'
+                | '+'
+                | '*'
+                | '?'
+                | '('
+                | ')'
+                | '|'
+                | '['
+                | ']'
+                | '{'
+                | '}'
+                | '^'
+                | '$'
+                | '#'
+                | '&'
+                | '-'
+                | '~'
+        ) {
+            dst.push('\\');
+        }
+        dst.push(c);
+    }
+}
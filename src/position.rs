@@ -0,0 +1,116 @@
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use crate::item::{Item, Operator, Varspec};
+
+/// The part of a URI that a variable expands into.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Position {
+    Path,
+    Query,
+    Fragment,
+}
+
+/// A use of a variable in a template, see
+/// [`UriTemplate::variable_usages`](crate::UriTemplate::variable_usages).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VariableUsage<'a> {
+    varspec: &'a Varspec,
+    operator: Option<Operator>,
+    position: Position,
+    span: Range<usize>,
+}
+
+impl<'a> VariableUsage<'a> {
+    pub fn name(&self) -> &'a str {
+        self.varspec.varname()
+    }
+
+    pub fn varspec(&self) -> &'a Varspec {
+        self.varspec
+    }
+
+    /// The operator of the expression, or `None` for simple string expansion.
+    pub fn operator(&self) -> Option<Operator> {
+        self.operator
+    }
+
+    pub fn is_exploded(&self) -> bool {
+        self.varspec.is_exploded()
+    }
+
+    /// The maximum number of characters to expand, if the variable has a prefix modifier.
+    pub fn prefix(&self) -> Option<usize> {
+        self.varspec.prefix()
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// The byte range of the varspec, e.g. `x:3`, in the template string.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+/// Every use of a variable, in order of appearance.
+///
+/// Variables are in the path until a literal `?` or a query expression starts the query, and in
+/// the fragment from a literal `#` or a fragment expression on. Spans are offsets in the string
+/// form of the template, which is the source text for parsed templates.
+pub fn variable_usages(items: &[Item]) -> Vec<VariableUsage<'_>> {
+    let mut usages = Vec::new();
+    let mut position = Position::Path;
+    let mut offset = 0;
+    for item in items {
+        match item {
+            Item::Literal(literal) => {
+                if literal.contains('#') {
+                    position = Position::Fragment;
+                } else if literal.contains('?') {
+                    position = position.max(Position::Query);
+                }
+                offset += literal.len();
+            }
+            Item::Expression(expression) => {
+                position = match expression.operator {
+                    Some(Operator::FormQuery) | Some(Operator::FormContinuation) => {
+                        position.max(Position::Query)
+                    }
+                    Some(Operator::Fragment) => Position::Fragment,
+                    _ => position,
+                };
+                // Skips `{` and the operator, then each varspec and its trailing `,` or `}`.
+                offset += 1 + expression.operator.map_or(0, |_| 1);
+                for varspec in &expression.variable_list {
+                    let end = offset + display_len(varspec);
+                    usages.push(VariableUsage {
+                        varspec,
+                        operator: expression.operator,
+                        position,
+                        span: offset..end,
+                    });
+                    offset = end + 1;
+                }
+            }
+        }
+    }
+    usages
+}
+
+/// The length in bytes of the `Display` form of `value`.
+pub fn display_len(value: &impl fmt::Display) -> usize {
+    struct Counter(usize);
+
+    impl Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    write!(counter, "{}", value).expect("counting never fails");
+    counter.0
+}
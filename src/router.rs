@@ -0,0 +1,252 @@
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fmt;
+
+use crate::expand::get_operator_table;
+use crate::item::{Item, ModifierLevel4};
+use crate::{UriTemplate, Value};
+
+/// Routes URIs to the most specific of a set of templates.
+///
+/// Templates are ranked by the number of literal characters (more is better), then by the number
+/// of expressions (fewer is better), then by the number of exploded variables (fewer is better).
+/// Templates with the same rank are tried in insertion order.
+#[derive(Clone, Debug)]
+pub struct Router<T> {
+    routes: Vec<Route<T>>,
+}
+
+#[derive(Clone, Debug)]
+struct Route<T> {
+    uri_template: UriTemplate,
+    value: T,
+    rank: Rank,
+    prefix: String,
+    suffix: String,
+}
+
+type Rank = (Reverse<usize>, usize, usize);
+
+#[derive(Debug)]
+pub struct Match<'a, T> {
+    uri_template: &'a UriTemplate,
+    value: &'a T,
+    variables: Vec<(String, Value)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RouteConflict {
+    existing: UriTemplate,
+}
+
+impl<T> Router<T> {
+    pub fn new() -> Self {
+        Router { routes: Vec::new() }
+    }
+
+    /// Adds a route, unless an existing template matches exactly the same URIs.
+    ///
+    /// Two templates match the same URIs when they only differ in the names of variables that do
+    /// not appear in the expansion, e.g. `/users/{id}` and `/users/{user_id}`.
+    pub fn insert(&mut self, uri_template: UriTemplate, value: T) -> Result<(), RouteConflict> {
+        let items = shape(&uri_template.0);
+        if let Some(route) = self
+            .routes
+            .iter()
+            .find(|route| items == shape(&route.uri_template.0))
+        {
+            let existing = route.uri_template.clone();
+            return Err(RouteConflict { existing });
+        }
+        let rank = rank(&uri_template.0);
+        let (prefix, suffix) = affixes(&uri_template.0);
+        let i = self.routes.partition_point(|route| route.rank <= rank);
+        let route = Route {
+            uri_template,
+            value,
+            rank,
+            prefix,
+            suffix,
+        };
+        self.routes.insert(i, route);
+        Ok(())
+    }
+
+    /// Finds the most specific template that unambiguously matches `uri`.
+    ///
+    /// Templates whose leading or trailing literal does not match `uri` are skipped without
+    /// running the matcher.
+    pub fn route(&self, uri: &str) -> Option<Match<'_, T>> {
+        self.routes.iter().find_map(|route| {
+            if !(uri.starts_with(&route.prefix) && uri.ends_with(&route.suffix)) {
+                return None;
+            }
+            let variables = route.uri_template.match_uri(uri).ok()?;
+            Some(Match {
+                uri_template: &route.uri_template,
+                value: &route.value,
+                variables,
+            })
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+impl<'a, T> Match<'a, T> {
+    pub fn uri_template(&self) -> &'a UriTemplate {
+        self.uri_template
+    }
+
+    pub fn value(&self) -> &'a T {
+        self.value
+    }
+
+    pub fn variables(&self) -> &[(String, Value)] {
+        &self.variables
+    }
+
+    pub fn into_variables(self) -> Vec<(String, Value)> {
+        self.variables
+    }
+}
+
+impl RouteConflict {
+    pub fn existing(&self) -> &UriTemplate {
+        &self.existing
+    }
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "template matches the same URIs as existing route `{}`",
+            self.existing
+        )
+    }
+}
+
+impl Error for RouteConflict {}
+
+fn rank(items: &[Item]) -> Rank {
+    let mut literal = 0;
+    let mut expressions = 0;
+    let mut exploded = 0;
+    for item in items {
+        match item {
+            Item::Literal(s) => literal += s.chars().count(),
+            Item::Expression(expression) => {
+                expressions += 1;
+                exploded += expression
+                    .variable_list
+                    .iter()
+                    .filter(|v| v.modifier_level4 == Some(ModifierLevel4::Explode))
+                    .count();
+            }
+        }
+    }
+    (Reverse(literal), expressions, exploded)
+}
+
+/// The leading and trailing literals, which every matching URI starts and ends with.
+fn affixes(items: &[Item]) -> (String, String) {
+    let literal = |item: Option<&Item>| match item {
+        Some(Item::Literal(s)) => s.clone(),
+        _ => String::new(),
+    };
+    (literal(items.first()), literal(items.last()))
+}
+
+/// Erases the names of variables that do not appear in the expansion.
+fn shape(items: &[Item]) -> Vec<Item> {
+    let mut items = items.to_vec();
+    for item in &mut items {
+        if let Item::Expression(expression) = item {
+            if !get_operator_table(expression.operator).named {
+                for varspec in &mut expression.variable_list {
+                    varspec.varname.clear();
+                }
+            }
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router() -> Router<&'static str> {
+        let mut router = Router::new();
+        for template in [
+            "/users/{id}",
+            "/users/me",
+            "/users/{id}/posts{?page,limit}",
+            "/users{/path*}",
+            "/{+path}",
+        ] {
+            router
+                .insert(UriTemplate::parse(template), template)
+                .unwrap();
+        }
+        router
+    }
+
+    #[test]
+    fn test_route() {
+        let router = router();
+
+        let left = router.route("/users/me").unwrap();
+        assert_eq!(*left.value(), "/users/me");
+        assert_eq!(left.variables(), []);
+
+        let left = router.route("/users/42").unwrap();
+        assert_eq!(*left.value(), "/users/{id}");
+        assert_eq!(
+            left.variables(),
+            [("id".to_string(), Value::from_string("42"))]
+        );
+
+        let left = router.route("/users/42/posts?limit=10").unwrap();
+        assert_eq!(*left.value(), "/users/{id}/posts{?page,limit}");
+        let right = [
+            ("id".to_string(), Value::from_string("42")),
+            ("limit".to_string(), Value::from_string("10")),
+        ];
+        assert_eq!(left.variables(), right);
+
+        let left = router.route("/users/42/comments/7").unwrap();
+        assert_eq!(*left.value(), "/users{/path*}");
+
+        let left = router.route("/about").unwrap();
+        assert_eq!(*left.value(), "/{+path}");
+
+        assert!(router.route("about").is_none());
+    }
+
+    #[test]
+    fn test_insert_conflict() {
+        let mut router = router();
+
+        let left = router.insert(UriTemplate::parse("/users/{user_id}"), "");
+        let existing = UriTemplate::parse("/users/{id}");
+        assert_eq!(left, Err(RouteConflict { existing }));
+
+        let left = router.insert(UriTemplate::parse("/users/{id}/posts{?page,size}"), "");
+        assert_eq!(left, Ok(()));
+        assert_eq!(router.len(), 6);
+    }
+}
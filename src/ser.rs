@@ -0,0 +1,540 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use serde::ser::{self, Serialize};
+
+use crate::value_ref::{with_scalar, ValueRef};
+use crate::{Value, Variables};
+
+/// Converts a struct or map into template variables.
+///
+/// Each field or entry becomes a variable: strings, characters and unit enum variants become
+/// [`Value::String`], numbers and booleans become the matching scalars, sequences become
+/// [`Value::List`] and nested structs or maps become [`Value::AssociativeArray`], at any depth.
+/// `None` and unit values are undefined and are left out, both as variables and as items of
+/// lists and associative arrays. Unsigned integers that do not fit in an `i64` become strings.
+///
+/// The result implements [`Variables`], so it can be passed straight to
+/// [`UriTemplate::expand`](crate::UriTemplate::expand). For many variables, [`Serialized`] looks
+/// them up faster.
+pub fn to_variables<T>(value: &T) -> Result<Vec<(String, Value)>, SerializeError>
+where
+    T: Serialize + ?Sized,
+{
+    match value.serialize(ValueSerializer)? {
+        Value::AssociativeArray(entries) => Ok(entries),
+        Value::Undefined => Ok(Vec::new()),
+        _ => Err(SerializeError::new("expected a struct or map")),
+    }
+}
+
+/// A struct or map serialized once into template variables, like [`to_variables`], and indexed
+/// by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Serialized {
+    variables: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SerializeError(String);
+
+impl Serialized {
+    pub fn new<T>(value: &T) -> Result<Self, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let variables = to_variables(value)?.into_iter().collect();
+        Ok(Serialized { variables })
+    }
+}
+
+impl<'a> Variables<'a, &'a Value> for Serialized {
+    fn get(&'a self, k: &'a str) -> Option<&'a Value> {
+        self.variables.get(k)
+    }
+}
+
+impl SerializeError {
+    fn new<T>(msg: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        SerializeError(msg.to_string())
+    }
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for SerializeError {}
+
+impl ser::Error for SerializeError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        SerializeError::new(msg)
+    }
+}
+
+struct ValueSerializer;
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+struct MapSerializer {
+    variant: Option<&'static str>,
+    entries: Vec<(String, Value)>,
+    key: Option<String>,
+}
+
+fn tagged(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        None => value,
+        Some(variant) => Value::AssociativeArray(vec![(variant.to_string(), value)]),
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerializeError> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SerializeError> {
+        Ok(Value::Integer(i64::from(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SerializeError> {
+        Ok(Value::Integer(i64::from(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SerializeError> {
+        Ok(Value::Integer(i64::from(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SerializeError> {
+        Ok(Value::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SerializeError> {
+        Ok(Value::Integer(i64::from(v)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SerializeError> {
+        Ok(Value::Integer(i64::from(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SerializeError> {
+        Ok(Value::Integer(i64::from(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SerializeError> {
+        Ok(match i64::try_from(v) {
+            Ok(v) => Value::Integer(v),
+            Err(_) => Value::String(v.to_string()),
+        })
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SerializeError> {
+        Ok(Value::Float(f64::from(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SerializeError> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SerializeError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SerializeError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Value, SerializeError> {
+        Err(SerializeError::new("bytes are not supported"))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerializeError> {
+        Ok(Value::Undefined)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerializeError> {
+        Ok(Value::Undefined)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerializeError> {
+        Ok(Value::Undefined)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerializeError> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(self)?;
+        Ok(tagged(Some(variant), value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SerializeError> {
+        Ok(SeqSerializer {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, SerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, SerializeError> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, SerializeError> {
+        Ok(MapSerializer {
+            variant: None,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, SerializeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, SerializeError> {
+        Ok(MapSerializer {
+            variant: Some(variant),
+            entries: Vec::with_capacity(len),
+            key: None,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(ValueSerializer)?;
+        if value != Value::Undefined {
+            self.items.push(value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        Ok(tagged(self.variant, Value::List(self.items)))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = key.serialize(ValueSerializer)?;
+        match with_scalar(ValueRef::from(&key), str::to_string) {
+            Some(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            None => Err(SerializeError::new("map keys must be strings or numbers")),
+        }
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerializeError::new("map value without a key"))?;
+        let value = value.serialize(ValueSerializer)?;
+        if value != Value::Undefined {
+            self.entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        Ok(tagged(self.variant, Value::AssociativeArray(self.entries)))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(ValueSerializer)?;
+        if value != Value::Undefined {
+            self.entries.push((key.to_string(), value));
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::UriTemplate;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    enum Sort {
+        MostLiked,
+    }
+
+    #[derive(Serialize)]
+    struct Request {
+        id: u64,
+        q: &'static str,
+        page: Option<u32>,
+        sort: Sort,
+        tags: Vec<&'static str>,
+        filter: BTreeMap<&'static str, Option<f64>>,
+    }
+
+    #[test]
+    fn test_to_variables() {
+        let request = Request {
+            id: 42,
+            q: "A B",
+            page: None,
+            sort: Sort::MostLiked,
+            tags: vec!["x", "y"],
+            filter: vec![("max", Some(2.5)), ("min", None)]
+                .into_iter()
+                .collect(),
+        };
+        let left = to_variables(&request).unwrap();
+        let right = vec![
+            ("id".to_string(), Value::Integer(42)),
+            ("q".to_string(), Value::from_string("A B")),
+            ("sort".to_string(), Value::from_string("most-liked")),
+            ("tags".to_string(), Value::from_list(["x", "y"])),
+            (
+                "filter".to_string(),
+                Value::AssociativeArray(vec![("max".to_string(), Value::Float(2.5))]),
+            ),
+        ];
+        assert_eq!(left, right);
+
+        let uri_template = UriTemplate::parse("/items/{id}{?q,page,sort,tags,filter*}");
+        let left = uri_template.expand(&left);
+        assert_eq!(left, "/items/42?q=A%20B&sort=most-liked&tags=x,y&max=2.5");
+    }
+
+    #[test]
+    fn test_serialized() {
+        let request = Request {
+            id: 42,
+            q: "A B",
+            page: None,
+            sort: Sort::MostLiked,
+            tags: vec!["x", "y"],
+            filter: vec![("max", Some(2.5)), ("min", None)]
+                .into_iter()
+                .collect(),
+        };
+        let variables = Serialized::new(&request).unwrap();
+        let uri_template = UriTemplate::parse("/items/{id}{?q,page,sort,tags,filter*,missing}");
+        let left = uri_template.expand(&variables);
+        assert_eq!(left, "/items/42?q=A%20B&sort=most-liked&tags=x,y&max=2.5");
+
+        let left = Serialized::new(&[1, 2]).unwrap_err();
+        assert_eq!(left.to_string(), "expected a struct or map");
+    }
+
+    #[test]
+    fn test_to_variables_map() {
+        let map: BTreeMap<_, _> = vec![("a", true), ("b", false)].into_iter().collect();
+        let left = to_variables(&map).unwrap();
+        let right = vec![
+            ("a".to_string(), Value::Boolean(true)),
+            ("b".to_string(), Value::Boolean(false)),
+        ];
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_to_variables_errors() {
+        let left = to_variables(&[1, 2]).unwrap_err();
+        assert_eq!(left.to_string(), "expected a struct or map");
+
+        let map: BTreeMap<_, _> = vec![(vec![1], 2)].into_iter().collect();
+        let left = to_variables(&map).unwrap_err();
+        assert_eq!(left.to_string(), "map keys must be strings or numbers");
+    }
+
+    #[test]
+    fn test_to_variables_nested() {
+        let nested: BTreeMap<_, _> = vec![("a", vec![vec![Some(1), None]])].into_iter().collect();
+        let left = to_variables(&nested).unwrap();
+        let right = vec![(
+            "a".to_string(),
+            Value::List(vec![Value::List(vec![Value::Integer(1)])]),
+        )];
+        assert_eq!(left, right);
+    }
+}
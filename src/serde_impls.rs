@@ -0,0 +1,229 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::{UriTemplate, Value};
+
+impl Serialize for UriTemplate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for UriTemplate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(UriTemplateVisitor)
+    }
+}
+
+struct UriTemplateVisitor;
+
+impl Visitor<'_> for UriTemplateVisitor {
+    type Value = UriTemplate;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a URI template")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        UriTemplate::try_parse(v).map_err(E::custom)
+    }
+}
+
+/// Values serialize as the matching JSON-like types, with `Undefined` as none.
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::AssociativeArray(value) => {
+                let mut map = serializer.serialize_map(Some(value.len()))?;
+                for (k, v) in value {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            Value::List(value) => {
+                let mut seq = serializer.serialize_seq(Some(value.len()))?;
+                for v in value {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            }
+            Value::String(value) => serializer.serialize_str(value),
+            Value::Integer(value) => serializer.serialize_i64(*value),
+            Value::Float(value) => serializer.serialize_f64(*value),
+            Value::Boolean(value) => serializer.serialize_bool(*value),
+            Value::Undefined => serializer.serialize_none(),
+        }
+    }
+}
+
+/// Null and unit deserialize to `Undefined`. Unsigned integers that do not fit in an `i64`
+/// deserialize to their string form.
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string, number, boolean, null, sequence or map")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(match i64::try_from(v) {
+            Ok(v) => Value::Integer(v),
+            Err(_) => Value::String(v.to_string()),
+        })
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Undefined)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Undefined)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut value = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(v) = seq.next_element()? {
+            value.push(v);
+        }
+        Ok(Value::List(value))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut value = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(entry) = map.next_entry()? {
+            value.push(entry);
+        }
+        Ok(Value::AssociativeArray(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_template() {
+        let uri_template = UriTemplate::parse("/users/{id}{?page,fields*}");
+        let json = serde_json::to_string(&uri_template).unwrap();
+        assert_eq!(json, r#""/users/{id}{?page,fields*}""#);
+        let left: UriTemplate = serde_json::from_str(&json).unwrap();
+        assert_eq!(left, uri_template);
+
+        let left = serde_json::from_str::<UriTemplate>(r#""/users/{!id}""#).unwrap_err();
+        assert_eq!(
+            left.to_string(),
+            "unknown operator at 8..9 at line 1 column 14"
+        );
+    }
+
+    #[test]
+    fn test_value() {
+        let json = r#"{"s":"a","l":["b","c"],"a":{"z":"d","y":"e"}}"#;
+        let left: Vec<(String, Value)> = serde_json::from_str::<indexmap::IndexMap<_, _>>(json)
+            .unwrap()
+            .into_iter()
+            .collect();
+        let right = vec![
+            ("s".to_string(), Value::from_string("a")),
+            ("l".to_string(), Value::from_list(["b", "c"])),
+            ("a".to_string(), Value::from_assoc([("z", "d"), ("y", "e")])),
+        ];
+        assert_eq!(left, right);
+
+        let left: indexmap::IndexMap<_, _> = right.into_iter().collect();
+        assert_eq!(serde_json::to_string(&left).unwrap(), json);
+    }
+
+    #[test]
+    fn test_value_scalars() {
+        let json = "[1,-2,3.5,true,null,18446744073709551615]";
+        let left: Value = serde_json::from_str(json).unwrap();
+        let right = Value::List(vec![
+            Value::Integer(1),
+            Value::Integer(-2),
+            Value::Float(3.5),
+            Value::Boolean(true),
+            Value::Undefined,
+            Value::from_string("18446744073709551615"),
+        ]);
+        assert_eq!(left, right);
+        assert_eq!(
+            serde_json::to_string(&left).unwrap(),
+            r#"[1,-2,3.5,true,null,"18446744073709551615"]"#
+        );
+    }
+
+    #[test]
+    fn test_value_nested() {
+        let json = r#"{"a":{"b":[1,{"c":"d"}]}}"#;
+        let left: Value = serde_json::from_str(json).unwrap();
+        let right = Value::AssociativeArray(vec![(
+            "a".to_string(),
+            Value::AssociativeArray(vec![(
+                "b".to_string(),
+                Value::List(vec![Value::Integer(1), Value::from_assoc([("c", "d")])]),
+            )]),
+        )]);
+        assert_eq!(left, right);
+        assert_eq!(serde_json::to_string(&left).unwrap(), json);
+    }
+}
@@ -0,0 +1,562 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::Value;
+
+/// A borrowed [`Value`], so that variables can be expanded without copying them.
+///
+/// Lists and associative arrays are trait objects, so that any collection can be expanded in
+/// place, see [`List`] and [`AssociativeArray`].
+#[derive(Clone, Copy)]
+pub enum ValueRef<'a> {
+    AssociativeArray(&'a dyn AssociativeArray),
+    List(&'a dyn List),
+    String(&'a str),
+    Integer(i64),
+    /// A scalar that expands to its `Display` form, e.g. an integer that does not fit in an
+    /// `i64`.
+    Formatted(&'a dyn fmt::Display),
+    Float(f64),
+    Boolean(bool),
+    Undefined,
+}
+
+/// A list that can be expanded without collecting it into a [`Value::List`].
+pub trait List {
+    /// Calls `f` with each item in order, stopping at the first error.
+    fn for_each_item(&self, f: &mut dyn FnMut(ValueRef<'_>) -> fmt::Result) -> fmt::Result;
+}
+
+/// An associative array that can be expanded without collecting it into a
+/// [`Value::AssociativeArray`].
+pub trait AssociativeArray {
+    /// Calls `f` with each entry in order, stopping at the first error.
+    fn for_each_entry(&self, f: &mut dyn FnMut(&str, ValueRef<'_>) -> fmt::Result) -> fmt::Result;
+}
+
+/// Borrows a value as a [`ValueRef`]. This is the bound on the values returned by
+/// [`Variables`](crate::Variables).
+///
+/// Besides [`Value`], this is implemented for strings, booleans, floats, integers, `Option` (where `None` is undefined), vectors, arrays and slices as lists, and maps as
+/// associative arrays.
+pub trait AsValueRef {
+    fn as_value_ref(&self) -> ValueRef<'_>;
+}
+
+impl fmt::Debug for ValueRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueRef::AssociativeArray(_) => f.write_str("AssociativeArray(..)"),
+            ValueRef::List(_) => f.write_str("List(..)"),
+            ValueRef::String(v) => f.debug_tuple("String").field(v).finish(),
+            ValueRef::Integer(v) => f.debug_tuple("Integer").field(v).finish(),
+            ValueRef::Formatted(v) => f
+                .debug_tuple("Formatted")
+                .field(&format_args!("{}", v))
+                .finish(),
+            ValueRef::Float(v) => f.debug_tuple("Float").field(v).finish(),
+            ValueRef::Boolean(v) => f.debug_tuple("Boolean").field(v).finish(),
+            ValueRef::Undefined => f.write_str("Undefined"),
+        }
+    }
+}
+
+impl<'a> From<&'a Value> for ValueRef<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::AssociativeArray(v) => ValueRef::AssociativeArray(v),
+            Value::List(v) => ValueRef::List(v),
+            Value::String(v) => ValueRef::String(v),
+            Value::Integer(v) => ValueRef::Integer(*v),
+            Value::Float(v) => ValueRef::Float(*v),
+            Value::Boolean(v) => ValueRef::Boolean(*v),
+            Value::Undefined => ValueRef::Undefined,
+        }
+    }
+}
+
+impl<'a> From<ValueRef<'a>> for Value {
+    /// Copies a borrowed value, keeping only the defined items of lists and associative arrays.
+    fn from(value: ValueRef<'a>) -> Self {
+        match value {
+            ValueRef::AssociativeArray(v) => {
+                let mut entries = Vec::new();
+                let _ = v.for_each_entry(&mut |k, v| {
+                    entries.push((k.to_string(), Value::from(v)));
+                    Ok(())
+                });
+                Value::AssociativeArray(entries)
+            }
+            ValueRef::List(v) => {
+                let mut items = Vec::new();
+                let _ = v.for_each_item(&mut |v| {
+                    items.push(Value::from(v));
+                    Ok(())
+                });
+                Value::List(items)
+            }
+            ValueRef::String(v) => Value::String(v.to_string()),
+            ValueRef::Integer(v) => Value::Integer(v),
+            ValueRef::Formatted(v) => Value::String(v.to_string()),
+            ValueRef::Float(v) => Value::Float(v),
+            ValueRef::Boolean(v) => Value::Boolean(v),
+            ValueRef::Undefined => Value::Undefined,
+        }
+    }
+}
+
+impl AsValueRef for ValueRef<'_> {
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        *self
+    }
+}
+
+impl AsValueRef for Value {
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::from(self)
+    }
+}
+
+impl<T> AsValueRef for &T
+where
+    T: AsValueRef + ?Sized,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        (**self).as_value_ref()
+    }
+}
+
+macro_rules! impl_as_value_ref_deref {
+    ($($t:ident),*) => {
+        $(
+            impl<T> AsValueRef for $t<T>
+            where
+                T: AsValueRef + ?Sized,
+            {
+                fn as_value_ref(&self) -> ValueRef<'_> {
+                    (**self).as_value_ref()
+                }
+            }
+        )*
+    };
+}
+
+impl_as_value_ref_deref!(Box, Rc, Arc);
+
+impl<T> AsValueRef for Option<T>
+where
+    T: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        match self {
+            Some(v) => v.as_value_ref(),
+            None => ValueRef::Undefined,
+        }
+    }
+}
+
+impl AsValueRef for str {
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::String(self)
+    }
+}
+
+impl AsValueRef for String {
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::String(self)
+    }
+}
+
+impl AsValueRef for Cow<'_, str> {
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::String(self)
+    }
+}
+
+impl AsValueRef for bool {
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::Boolean(*self)
+    }
+}
+
+macro_rules! impl_as_value_ref {
+    ($variant:ident($t:ty): $($from:ty),*) => {
+        $(
+            impl AsValueRef for $from {
+                fn as_value_ref(&self) -> ValueRef<'_> {
+                    ValueRef::$variant(<$t>::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+impl_as_value_ref!(Integer(i64): i8, i16, i32, i64, u8, u16, u32);
+impl_as_value_ref!(Float(f64): f32, f64);
+
+/// Integers that may not fit in an `i64` fall back to their `Display` form.
+macro_rules! impl_as_value_ref_wide_integer {
+    ($($t:ty),*) => {
+        $(
+            impl AsValueRef for $t {
+                fn as_value_ref(&self) -> ValueRef<'_> {
+                    match i64::try_from(*self) {
+                        Ok(v) => ValueRef::Integer(v),
+                        Err(_) => ValueRef::Formatted(self),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_as_value_ref_wide_integer!(i128, isize, u64, u128, usize);
+
+impl<T> List for Vec<T>
+where
+    T: AsValueRef,
+{
+    fn for_each_item(&self, f: &mut dyn FnMut(ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        self.iter().try_for_each(|v| f(v.as_value_ref()))
+    }
+}
+
+impl<T> List for &[T]
+where
+    T: AsValueRef,
+{
+    fn for_each_item(&self, f: &mut dyn FnMut(ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        self.iter().try_for_each(|v| f(v.as_value_ref()))
+    }
+}
+
+impl<T, const N: usize> List for [T; N]
+where
+    T: AsValueRef,
+{
+    fn for_each_item(&self, f: &mut dyn FnMut(ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        self.iter().try_for_each(|v| f(v.as_value_ref()))
+    }
+}
+
+impl<T> AsValueRef for Vec<T>
+where
+    T: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::List(self)
+    }
+}
+
+impl<T> AsValueRef for &[T]
+where
+    T: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::List(self)
+    }
+}
+
+impl<T, const N: usize> AsValueRef for [T; N]
+where
+    T: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::List(self)
+    }
+}
+
+impl<K, V> AssociativeArray for Vec<(K, V)>
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn for_each_entry(&self, f: &mut dyn FnMut(&str, ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        self.iter()
+            .try_for_each(|(k, v)| f(k.as_ref(), v.as_value_ref()))
+    }
+}
+
+impl<K, V> AssociativeArray for BTreeMap<K, V>
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn for_each_entry(&self, f: &mut dyn FnMut(&str, ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        self.iter()
+            .try_for_each(|(k, v)| f(k.as_ref(), v.as_value_ref()))
+    }
+}
+
+impl<K, V, S> AssociativeArray for HashMap<K, V, S>
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn for_each_entry(&self, f: &mut dyn FnMut(&str, ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        self.iter()
+            .try_for_each(|(k, v)| f(k.as_ref(), v.as_value_ref()))
+    }
+}
+
+impl<K, V> AsValueRef for BTreeMap<K, V>
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::AssociativeArray(self)
+    }
+}
+
+impl<K, V, S> AsValueRef for HashMap<K, V, S>
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::AssociativeArray(self)
+    }
+}
+
+/// A list backed by an iterator, which is consumed as the list is expanded, e.g. rows from a
+/// database cursor.
+///
+/// An item for which the callback fails is kept and passed first on the next call, so checking
+/// whether the list is defined only consumes the undefined items before the first defined one.
+/// An empty iterator is an undefined list.
+pub struct IterList<I>
+where
+    I: Iterator,
+{
+    state: RefCell<IterState<I>>,
+}
+
+/// An associative array backed by an iterator of entries, which is consumed like an
+/// [`IterList`].
+pub struct IterAssociativeArray<I>
+where
+    I: Iterator,
+{
+    state: RefCell<IterState<I>>,
+}
+
+struct IterState<I>
+where
+    I: Iterator,
+{
+    peeked: Option<I::Item>,
+    iter: I,
+}
+
+impl<I> IterState<I>
+where
+    I: Iterator,
+{
+    fn new<T>(iter: T) -> RefCell<Self>
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        RefCell::new(IterState {
+            peeked: None,
+            iter: iter.into_iter(),
+        })
+    }
+}
+
+/// Calls `f` with each remaining item, keeping the item for which `f` fails. The state is not
+/// borrowed while `f` runs.
+fn try_for_each_once<I>(
+    state: &RefCell<IterState<I>>,
+    mut f: impl FnMut(&I::Item) -> fmt::Result,
+) -> fmt::Result
+where
+    I: Iterator,
+{
+    loop {
+        let item = {
+            let mut state = state.borrow_mut();
+            match state.peeked.take().or_else(|| state.iter.next()) {
+                Some(item) => item,
+                None => return Ok(()),
+            }
+        };
+        if let Err(e) = f(&item) {
+            state.borrow_mut().peeked = Some(item);
+            return Err(e);
+        }
+    }
+}
+
+impl<I> IterList<I>
+where
+    I: Iterator,
+{
+    pub fn new<T>(iter: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        IterList {
+            state: IterState::new(iter),
+        }
+    }
+}
+
+impl<I> IterAssociativeArray<I>
+where
+    I: Iterator,
+{
+    pub fn new<T>(iter: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        IterAssociativeArray {
+            state: IterState::new(iter),
+        }
+    }
+}
+
+impl<I> fmt::Debug for IterList<I>
+where
+    I: Iterator,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("IterList(..)")
+    }
+}
+
+impl<I> fmt::Debug for IterAssociativeArray<I>
+where
+    I: Iterator,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("IterAssociativeArray(..)")
+    }
+}
+
+impl<I> List for IterList<I>
+where
+    I: Iterator,
+    I::Item: AsValueRef,
+{
+    fn for_each_item(&self, f: &mut dyn FnMut(ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        try_for_each_once(&self.state, |v| f(v.as_value_ref()))
+    }
+}
+
+impl<I, K, V> AssociativeArray for IterAssociativeArray<I>
+where
+    I: Iterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn for_each_entry(&self, f: &mut dyn FnMut(&str, ValueRef<'_>) -> fmt::Result) -> fmt::Result {
+        try_for_each_once(&self.state, |(k, v)| f(k.as_ref(), v.as_value_ref()))
+    }
+}
+
+impl<I> AsValueRef for IterList<I>
+where
+    I: Iterator,
+    I::Item: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::List(self)
+    }
+}
+
+impl<I, K, V> AsValueRef for IterAssociativeArray<I>
+where
+    I: Iterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        ValueRef::AssociativeArray(self)
+    }
+}
+
+/// Calls `f` with the string form of a defined scalar, e.g. `-42` for an integer, or returns
+/// `None` for undefined values, lists and associative arrays.
+pub fn with_scalar<R>(value: ValueRef<'_>, f: impl FnOnce(&str) -> R) -> Option<R> {
+    match value {
+        ValueRef::String(v) => Some(f(v)),
+        ValueRef::Integer(v) => {
+            let mut buf = IntegerBuf::default();
+            write!(buf, "{}", v).expect("an i64 fits in 20 bytes");
+            Some(f(buf.as_str()))
+        }
+        ValueRef::Formatted(v) => Some(f(&v.to_string())),
+        ValueRef::Float(v) if v.is_finite() => Some(f(&v.to_string())),
+        ValueRef::Boolean(v) => Some(f(if v { "true" } else { "false" })),
+        _ => None,
+    }
+}
+
+/// Whether `value` expands to anything: a defined scalar, or a list or associative array with at
+/// least one defined scalar item.
+pub fn is_defined(value: ValueRef<'_>) -> bool {
+    // Stops at the first defined item with an error.
+    match value {
+        ValueRef::AssociativeArray(v) => v
+            .for_each_entry(&mut |_, v| {
+                if is_defined_scalar(v) {
+                    Err(fmt::Error)
+                } else {
+                    Ok(())
+                }
+            })
+            .is_err(),
+        ValueRef::List(v) => v
+            .for_each_item(&mut |v| {
+                if is_defined_scalar(v) {
+                    Err(fmt::Error)
+                } else {
+                    Ok(())
+                }
+            })
+            .is_err(),
+        value => is_defined_scalar(value),
+    }
+}
+
+fn is_defined_scalar(value: ValueRef<'_>) -> bool {
+    match value {
+        ValueRef::String(_)
+        | ValueRef::Integer(_)
+        | ValueRef::Formatted(_)
+        | ValueRef::Boolean(_) => true,
+        ValueRef::Float(v) => v.is_finite(),
+        ValueRef::AssociativeArray(_) | ValueRef::List(_) | ValueRef::Undefined => false,
+    }
+}
+
+/// Formats an `i64` without allocating.
+#[derive(Default)]
+struct IntegerBuf {
+    buf: [u8; 20],
+    len: usize,
+}
+
+impl IntegerBuf {
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.buf[..self.len]).expect("integers are ASCII")
+    }
+}
+
+impl Write for IntegerBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
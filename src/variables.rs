@@ -0,0 +1,266 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::{AsValueRef, ValueRef, Variables};
+
+/// Variables looked up by a closure, see [`from_fn`].
+#[derive(Clone, Copy, Debug)]
+pub struct FromFn<F>(F);
+
+/// Looks up variables with a closure, e.g. `from_fn(|k| env::var(k).ok())`.
+pub fn from_fn<F, B>(f: F) -> FromFn<F>
+where
+    F: Fn(&str) -> Option<B>,
+    B: AsValueRef,
+{
+    FromFn(f)
+}
+
+impl<'a, F, B> Variables<'a, B> for FromFn<F>
+where
+    F: Fn(&str) -> Option<B>,
+    B: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<B> {
+        (self.0)(k)
+    }
+}
+
+/// Variables looked up in `first`, then in `second` for the names that `first` does not define,
+/// see [`chain`].
+#[derive(Clone, Copy, Debug)]
+pub struct Chain<A, B>(A, B);
+
+/// A value from either source of a [`Chain`].
+#[derive(Clone, Copy, Debug)]
+pub enum ChainValue<A, B> {
+    First(A),
+    Second(B),
+}
+
+/// Layers two variable sources, e.g. `chain(overrides, chain(request, defaults))`.
+///
+/// A name is looked up in `second` only when `first` returns `None`, so a variable that `first`
+/// sets to [`Value::Undefined`](crate::Value::Undefined) is undefined.
+pub fn chain<A, B>(first: A, second: B) -> Chain<A, B> {
+    Chain(first, second)
+}
+
+impl<'a, X, Y, B1, B2> Variables<'a, ChainValue<B1, B2>> for Chain<X, Y>
+where
+    X: Variables<'a, B1>,
+    Y: Variables<'a, B2>,
+    B1: AsValueRef,
+    B2: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<ChainValue<B1, B2>> {
+        match self.0.get(k) {
+            Some(v) => Some(ChainValue::First(v)),
+            None => self.1.get(k).map(ChainValue::Second),
+        }
+    }
+}
+
+impl<A, B> AsValueRef for ChainValue<A, B>
+where
+    A: AsValueRef,
+    B: AsValueRef,
+{
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        match self {
+            ChainValue::First(v) => v.as_value_ref(),
+            ChainValue::Second(v) => v.as_value_ref(),
+        }
+    }
+}
+
+/// No variables are defined.
+impl<'a> Variables<'a, ValueRef<'a>> for () {
+    fn get(&'a self, _: &'a str) -> Option<ValueRef<'a>> {
+        None
+    }
+}
+
+impl<'a, K, V> Variables<'a, &'a V> for [(K, V)]
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<&'a V> {
+        self.iter()
+            .find(|(k1, _)| k1.as_ref() == k)
+            .map(|(_, v1)| v1)
+    }
+}
+
+impl<'a, K, V, const N: usize> Variables<'a, &'a V> for [(K, V); N]
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<&'a V> {
+        Variables::get(&self[..], k)
+    }
+}
+
+impl<'a, K, V> Variables<'a, &'a V> for Vec<(K, V)>
+where
+    K: AsRef<str>,
+    V: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<&'a V> {
+        Variables::get(&self[..], k)
+    }
+}
+
+impl<'a, K, V, S> Variables<'a, &'a V> for HashMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash,
+    V: AsValueRef,
+    S: BuildHasher,
+{
+    fn get(&'a self, k: &'a str) -> Option<&'a V> {
+        HashMap::get(self, k)
+    }
+}
+
+impl<'a, K, V> Variables<'a, &'a V> for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<&'a V> {
+        BTreeMap::get(self, k)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<'a, K, V, S> Variables<'a, &'a V> for indexmap::IndexMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash,
+    V: AsValueRef,
+    S: BuildHasher,
+{
+    fn get(&'a self, k: &'a str) -> Option<&'a V> {
+        indexmap::IndexMap::get(self, k)
+    }
+}
+
+/// No variables are defined for `None`.
+impl<'a, T, B> Variables<'a, B> for Option<T>
+where
+    T: Variables<'a, B>,
+    B: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<B> {
+        self.as_ref().and_then(|v| v.get(k))
+    }
+}
+
+macro_rules! impl_variables_deref {
+    ($($t:ident),*) => {
+        $(
+            impl<'a, T, B> Variables<'a, B> for $t<T>
+            where
+                T: Variables<'a, B> + ?Sized,
+                B: AsValueRef,
+            {
+                fn get(&'a self, k: &'a str) -> Option<B> {
+                    (**self).get(k)
+                }
+            }
+        )*
+    };
+}
+
+impl_variables_deref!(Box, Rc, Arc);
+
+impl<'a, T, B> Variables<'a, B> for &T
+where
+    T: Variables<'a, B> + ?Sized,
+    B: AsValueRef,
+{
+    fn get(&'a self, k: &'a str) -> Option<B> {
+        (**self).get(k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UriTemplate, Value};
+
+    #[test]
+    fn test_maps() {
+        let uri_template = UriTemplate::parse("{x}{?y}");
+
+        let mut variables = HashMap::new();
+        variables.insert("x", "a");
+        assert_eq!(uri_template.expand(&variables), "a");
+
+        let mut variables = BTreeMap::new();
+        variables.insert("y".to_string(), vec![1, 2]);
+        assert_eq!(uri_template.expand(&variables), "?y=1,2");
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_index_map() {
+        let variables = indexmap::indexmap! { "x" => "a", "y" => "b" };
+        assert_eq!(UriTemplate::parse("{x}{?y}").expand(&variables), "a?y=b");
+    }
+
+    #[test]
+    fn test_slices() {
+        let uri_template = UriTemplate::parse("{x}{?y}");
+
+        let variables = [("x", "a"), ("y", "b")];
+        assert_eq!(uri_template.expand(&variables), "a?y=b");
+        assert_eq!(uri_template.expand(&variables[1..]), "?y=b");
+        assert_eq!(uri_template.expand(&vec![("y", true)]), "?y=true");
+    }
+
+    #[test]
+    fn test_wrappers() {
+        let uri_template = UriTemplate::parse("{x}");
+        let variables = vec![("x", Value::Integer(1))];
+
+        assert_eq!(uri_template.expand(&Some(variables.clone())), "1");
+        assert_eq!(uri_template.expand(&None::<Vec<(&str, Value)>>), "");
+        assert_eq!(uri_template.expand(&&variables), "1");
+        assert_eq!(uri_template.expand(&Box::new(variables.clone())), "1");
+        assert_eq!(uri_template.expand(&Rc::new(variables.clone())), "1");
+        assert_eq!(uri_template.expand(&Arc::new(variables)), "1");
+    }
+
+    #[test]
+    fn test_chain() {
+        let uri_template = UriTemplate::parse("{host}/v{version}{/id}{?q}");
+        let defaults = vec![("host", "example.com"), ("version", "1")];
+        let request = vec![("id".to_string(), Value::Integer(7))];
+        let overrides = from_fn(|k| match k {
+            "version" => Some(Value::Integer(2)),
+            "q" => Some(Value::Undefined),
+            _ => None,
+        });
+
+        let variables = chain(&overrides, chain(&request, &defaults));
+        assert_eq!(uri_template.expand(&variables), "example.com/v2/7");
+
+        let variables = chain(&request, &defaults);
+        assert_eq!(uri_template.expand(&variables), "example.com/v1/7");
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let uri_template = UriTemplate::parse("{x}{?y}");
+        let variables = from_fn(|k| match k {
+            "x" => Some(Value::from_string(k.to_uppercase())),
+            _ => None,
+        });
+        assert_eq!(uri_template.expand(&variables), "X");
+    }
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
@@ -7,42 +8,25 @@ use std::path::Path;
 
 use indexmap::IndexMap;
 use serde::Deserialize;
-use serde_json::{from_reader, Number};
+use serde_json::from_reader;
 
-use uri_template::{Expander, UriTemplate, Value, Variables};
-
-#[derive(Deserialize)]
-#[serde(untagged)]
-enum VariableValue {
-    Number(Number),
-    String(String),
-    Array(Vec<String>),
-    Object(IndexMap<String, String>),
-}
+use uri_template::{Expander, Level, Parser, UriTemplate, Value, Variables};
 
 #[derive(Deserialize)]
 struct Group {
     #[serde(default = "default_level")]
-    level: u32,
-    variables: IndexMap<String, VariableValue>,
+    level: u8,
+    variables: IndexMap<String, Value>,
     testcases: Vec<(String, serde_json::Value)>,
 }
 
-fn default_level() -> u32 {
+fn default_level() -> u8 {
     4
 }
 
-impl<'a> Variables<'a, Value> for Group {
-    fn get(&self, k: &str) -> Option<uri_template::Value> {
-        self.variables
-            .get(k)
-            .map(|v| match v {
-                VariableValue::Number(n) => Some(Value::from_string(n.to_string())),
-                VariableValue::String(s) => Some(Value::from_string(s)),
-                VariableValue::Array(a) => Some(Value::from_list(a)),
-                VariableValue::Object(o) => Some(Value::from_assoc(o)),
-            })
-            .flatten()
+impl<'a> Variables<'a, &'a Value> for Group {
+    fn get(&'a self, k: &str) -> Option<&'a Value> {
+        self.variables.get(k)
     }
 }
 
@@ -57,6 +41,11 @@ where
         for (t, v) in &g.testcases {
             let uri_template = UriTemplate::parse(t);
             let left = uri_template.expand(g);
+            if !matches!(v, serde_json::Value::Bool(false)) {
+                let level = Level::try_from(g.level).expect("levels are 1 to 4");
+                let uri_template = Parser::new().max_level(level).parse(t)?;
+                assert!(uri_template.level() <= level);
+            }
             match v {
                 serde_json::Value::String(right) => {
                     assert_eq!(left, *right);
@@ -0,0 +1,58 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, LitStr};
+
+mod template;
+mod typed;
+mod variables;
+
+/// Parses a URI template at compile time and evaluates to the `uri_template::UriTemplate`.
+///
+/// A malformed expression is a compile error that points at the template. The template is built
+/// directly from its parsed items, so there is no parsing at run time.
+#[proc_macro]
+pub fn uri_template(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    match template::parse(&lit) {
+        Ok(uri_template) => template::to_tokens(&uri_template).into(),
+        Err(error) => {
+            // A block, so that several errors are still an expression.
+            let error = error.into_compile_error();
+            quote::quote!({ #error }).into()
+        }
+    }
+}
+
+/// Derives `uri_template::Variables` for a struct with named fields.
+///
+/// Each field becomes a variable named after the field and is converted with
+/// `uri_template::ToValue`. Fields accept `#[uri(rename = "...")]` to use a different variable
+/// name and `#[uri(skip)]` to leave them out.
+///
+/// The generated `Variables` returns owned values, so each lookup clones the field, including
+/// strings, vectors and maps. To expand large fields in place, implement `Variables` by hand and
+/// return `uri_template::ValueRef`s, e.g. with `uri_template::AsValueRef`.
+#[proc_macro_derive(Variables, attributes(uri))]
+pub fn derive_variables(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    variables::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Turns a unit struct into a typed builder for a URI template.
+///
+/// Variables used in the path, before any query or fragment, are the parameters of `new`, so
+/// leaving one out does not compile. Every other variable gets a setter. Exploded variables take
+/// lists, with an extra `{name}_assoc` setter for associative arrays, and the rest take anything
+/// that implements `Display`. Setter names are the variable names with characters other than
+/// ASCII letters and digits replaced by `_`.
+///
+/// The struct implements `Display` with the expansion, and `uri_template::Variables`.
+#[proc_macro_attribute]
+pub fn typed_uri_template(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(attr as LitStr);
+    let item = parse_macro_input!(item as DeriveInput);
+    typed::expand(lit, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
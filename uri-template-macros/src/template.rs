@@ -0,0 +1,80 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+use uri_template::{Item, ModifierLevel4, Operator, ParseErrors, Parser, UriTemplate};
+
+/// Parses the template in `lit`, reporting every malformed expression at the literal.
+pub fn parse(lit: &LitStr) -> syn::Result<UriTemplate> {
+    Parser::new()
+        .parse_all(lit.value())
+        .map_err(|errors| to_error(lit, &errors))
+}
+
+/// One error per malformed expression, with carets under it since the span of a string literal
+/// cannot be narrowed on stable.
+fn to_error(lit: &LitStr, errors: &ParseErrors) -> syn::Error {
+    let template = errors.template();
+    errors
+        .errors()
+        .iter()
+        .map(|error| syn::Error::new(lit.span(), error.render(template)))
+        .reduce(|mut left, right| {
+            left.combine(right);
+            left
+        })
+        .expect("parse_all fails with at least one error")
+}
+
+/// Generates an expression that builds `uri_template` without parsing it again.
+pub fn to_tokens(uri_template: &UriTemplate) -> TokenStream {
+    let items = uri_template.items().map(|item| match item {
+        Item::Literal(literal) => quote! {
+            ::uri_template::Item::Literal(::std::string::String::from(#literal))
+        },
+        Item::Expression(expression) => {
+            let operator = match expression.operator() {
+                Some(operator) => {
+                    let operator = operator_tokens(operator);
+                    quote!(::std::option::Option::Some(#operator))
+                }
+                None => quote!(::std::option::Option::None),
+            };
+            let varspecs = expression.varspecs().iter().map(|varspec| {
+                let varname = varspec.varname();
+                let modifier = match varspec.modifier() {
+                    Some(ModifierLevel4::Prefix(size)) => quote! {
+                        ::std::option::Option::Some(::uri_template::ModifierLevel4::Prefix(#size))
+                    },
+                    Some(ModifierLevel4::Explode) => quote! {
+                        ::std::option::Option::Some(::uri_template::ModifierLevel4::Explode)
+                    },
+                    None => quote!(::std::option::Option::None),
+                };
+                quote! {
+                    ::uri_template::__private::varspec(::std::string::String::from(#varname), #modifier)
+                }
+            });
+            quote! {
+                ::uri_template::Item::Expression(::uri_template::__private::expression(
+                    #operator,
+                    ::std::vec![#(#varspecs),*],
+                ))
+            }
+        }
+    });
+    quote! {
+        ::uri_template::__private::uri_template(::std::vec![#(#items),*])
+    }
+}
+
+fn operator_tokens(operator: Operator) -> TokenStream {
+    match operator {
+        Operator::Reserved => quote!(::uri_template::Operator::Reserved),
+        Operator::Fragment => quote!(::uri_template::Operator::Fragment),
+        Operator::Label => quote!(::uri_template::Operator::Label),
+        Operator::PathSegment => quote!(::uri_template::Operator::PathSegment),
+        Operator::PathParameter => quote!(::uri_template::Operator::PathParameter),
+        Operator::FormQuery => quote!(::uri_template::Operator::FormQuery),
+        Operator::FormContinuation => quote!(::uri_template::Operator::FormContinuation),
+    }
+}
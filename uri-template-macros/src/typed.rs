@@ -0,0 +1,240 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{Data, DataStruct, DeriveInput, Fields, LitStr};
+use uri_template::{Position, UriTemplate};
+
+use crate::template;
+
+/// A variable of the template, merged over every expression that uses it.
+struct Variable {
+    varname: String,
+    ident: Ident,
+    /// Used in the path, before any query or fragment, so it must be set in `new`.
+    required: bool,
+    exploded: bool,
+}
+
+pub fn expand(lit: LitStr, item: DeriveInput) -> syn::Result<TokenStream> {
+    let is_unit = matches!(
+        item.data,
+        Data::Struct(DataStruct {
+            fields: Fields::Unit,
+            ..
+        })
+    );
+    if !is_unit || !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item,
+            "`typed_uri_template` expects a unit struct without generics",
+        ));
+    }
+    let uri_template = template::parse(&lit)?;
+    let variables = variables(&lit, &uri_template)?;
+    check_names(&lit, &variables)?;
+
+    let DeriveInput {
+        attrs, vis, ident, ..
+    } = &item;
+    let uri_template = template::to_tokens(&uri_template);
+    let fields = variables.iter().map(|variable| {
+        let ident = &variable.ident;
+        quote!(#ident: ::std::option::Option<::uri_template::Value>)
+    });
+    let required: Vec<_> = variables.iter().filter(|v| v.required).collect();
+    let params = required.iter().map(|variable| {
+        let ident = &variable.ident;
+        if variable.exploded {
+            quote!(#ident: impl ::std::iter::IntoIterator<Item = impl ::std::fmt::Display>)
+        } else {
+            quote!(#ident: impl ::std::fmt::Display)
+        }
+    });
+    let inits = variables.iter().map(|variable| {
+        let ident = &variable.ident;
+        if !variable.required {
+            quote!(#ident: ::std::option::Option::None)
+        } else if variable.exploded {
+            let value = list(ident);
+            quote!(#ident: ::std::option::Option::Some(#value))
+        } else {
+            let value = string(ident);
+            quote!(#ident: ::std::option::Option::Some(#value))
+        }
+    });
+    let setters = variables.iter().filter(|v| !v.required).map(|variable| {
+        let ident = &variable.ident;
+        if variable.exploded {
+            let assoc = assoc(ident);
+            let value = list(ident);
+            quote! {
+                #vis fn #ident<I>(mut self, #ident: I) -> Self
+                where
+                    I: ::std::iter::IntoIterator,
+                    I::Item: ::std::fmt::Display,
+                {
+                    self.#ident = ::std::option::Option::Some(#value);
+                    self
+                }
+
+                #vis fn #assoc<I, K, V>(mut self, #ident: I) -> Self
+                where
+                    I: ::std::iter::IntoIterator<Item = (K, V)>,
+                    K: ::std::fmt::Display,
+                    V: ::std::fmt::Display,
+                {
+                    self.#ident = ::std::option::Option::Some(::uri_template::Value::from_assoc(
+                        #ident.into_iter().map(|(k, v)| (k.to_string(), v.to_string())),
+                    ));
+                    self
+                }
+            }
+        } else {
+            let value = string(ident);
+            quote! {
+                #vis fn #ident(mut self, #ident: impl ::std::fmt::Display) -> Self {
+                    self.#ident = ::std::option::Option::Some(#value);
+                    self
+                }
+            }
+        }
+    });
+    let arms = variables.iter().map(|variable| {
+        let varname = &variable.varname;
+        let ident = &variable.ident;
+        quote!(#varname => self.#ident.as_ref(),)
+    });
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis struct #ident {
+            #(#fields,)*
+        }
+
+        impl #ident {
+            #[allow(clippy::new_without_default)]
+            #vis fn new(#(#params),*) -> Self {
+                #ident {
+                    #(#inits,)*
+                }
+            }
+
+            #(#setters)*
+
+            #vis fn uri_template() -> &'static ::uri_template::UriTemplate {
+                static URI_TEMPLATE: ::std::sync::OnceLock<::uri_template::UriTemplate> =
+                    ::std::sync::OnceLock::new();
+                URI_TEMPLATE.get_or_init(|| #uri_template)
+            }
+        }
+
+        impl<'a> ::uri_template::Variables<'a, &'a ::uri_template::Value> for #ident {
+            fn get(&'a self, k: &'a str) -> ::std::option::Option<&'a ::uri_template::Value> {
+                match k {
+                    #(#arms)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for #ident {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                Self::uri_template().expand_to(self, f)
+            }
+        }
+    })
+}
+
+fn string(ident: &Ident) -> TokenStream {
+    quote!(::uri_template::Value::String(#ident.to_string()))
+}
+
+fn list(ident: &Ident) -> TokenStream {
+    quote! {
+        ::uri_template::Value::from_list(#ident.into_iter().map(|v| v.to_string()))
+    }
+}
+
+/// The setter of an exploded variable that takes key-value pairs.
+fn assoc(ident: &Ident) -> Ident {
+    format_ident!("{}_assoc", ident.unraw())
+}
+
+/// Collects the variables in order of first appearance.
+///
+/// A variable is required if it is used in the path, before the first query or fragment.
+fn variables(lit: &LitStr, uri_template: &UriTemplate) -> syn::Result<Vec<Variable>> {
+    let mut variables: Vec<Variable> = Vec::new();
+    for usage in uri_template.variable_usages() {
+        let required = usage.position() == Position::Path;
+        match variables.iter_mut().find(|v| v.varname == usage.name()) {
+            Some(variable) => {
+                variable.required |= required;
+                variable.exploded |= usage.is_exploded();
+            }
+            None => {
+                let ident = to_ident(usage.name()).ok_or_else(|| {
+                    let message = format!(
+                        "variable `{}` cannot be used as an identifier",
+                        usage.name()
+                    );
+                    syn::Error::new(lit.span(), message)
+                })?;
+                variables.push(Variable {
+                    varname: usage.name().to_string(),
+                    ident,
+                    required,
+                    exploded: usage.is_exploded(),
+                });
+            }
+        }
+    }
+    Ok(variables)
+}
+
+/// Checks that the fields and setters of different variables do not have the same name, and do
+/// not clash with the other generated methods.
+fn check_names(lit: &LitStr, variables: &[Variable]) -> syn::Result<()> {
+    let mut names: Vec<(Ident, &Variable)> = Vec::new();
+    for variable in variables {
+        let ident = variable.ident.unraw();
+        if ident == "new" || ident == "uri_template" {
+            let message = format!(
+                "variable `{}` clashes with the generated method `{}`",
+                variable.varname, ident
+            );
+            return Err(syn::Error::new(lit.span(), message));
+        }
+        let mut idents = vec![ident];
+        if variable.exploded && !variable.required {
+            idents.push(assoc(&variable.ident));
+        }
+        for ident in idents {
+            if let Some((_, other)) = names.iter().find(|(name, _)| *name == ident) {
+                let message = format!(
+                    "variables `{}` and `{}` have the same setter name `{}`",
+                    other.varname, variable.varname, ident
+                );
+                return Err(syn::Error::new(lit.span(), message));
+            }
+            names.push((ident, variable));
+        }
+    }
+    Ok(())
+}
+
+/// Makes an identifier from a variable name, which may contain `.` and pct-encoded triplets, or
+/// `None` for names such as `self` that cannot be identifiers even as raw identifiers.
+fn to_ident(varname: &str) -> Option<Ident> {
+    let mut s: String = varname
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if s.starts_with(|c: char| c.is_ascii_digit()) {
+        s.insert(0, '_');
+    }
+    match s.as_str() {
+        "_" | "self" | "Self" | "super" | "crate" => None,
+        _ => Some(syn::parse_str(&s).unwrap_or_else(|_| Ident::new_raw(&s, Span::call_site()))),
+    }
+}
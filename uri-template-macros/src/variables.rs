@@ -0,0 +1,88 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Data, DeriveInput, Fields, LitStr};
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unit => return derive_fields(&input, Vec::new()),
+            Fields::Unnamed(_) => return Err(unsupported(&input)),
+        },
+        _ => return Err(unsupported(&input)),
+    };
+    let mut variables = Vec::new();
+    for field in fields {
+        let mut name = None;
+        let mut skip = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("uri"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let s: LitStr = meta.value()?.parse()?;
+                    name = Some(s.value());
+                    Ok(())
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename` or `skip`"))
+                }
+            })?;
+        }
+        if skip {
+            continue;
+        }
+        let ident = field.ident.clone().expect("named field");
+        let name = name.unwrap_or_else(|| ident.to_string());
+        variables.push((name, ident, field.ty.clone()));
+    }
+    derive_fields(&input, variables)
+}
+
+fn derive_fields(
+    input: &DeriveInput,
+    variables: Vec<(String, syn::Ident, syn::Type)>,
+) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let mut generics = input.generics.clone();
+    {
+        let where_clause = generics.make_where_clause();
+        for (_, _, ty) in &variables {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::uri_template::ToValue));
+        }
+    }
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+    let mut impl_generics = generics.clone();
+    impl_generics.params.insert(0, parse_quote!('__uri));
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+    let arms = variables.iter().map(|(name, ident, _)| {
+        quote! {
+            #name => ::uri_template::ToValue::to_value(&self.#ident),
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics ::uri_template::Variables<'__uri, ::uri_template::Value>
+            for #ident #ty_generics #where_clause
+        {
+            fn get(&'__uri self, k: &'__uri str) -> ::std::option::Option<::uri_template::Value> {
+                match k {
+                    #(#arms)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+    })
+}
+
+fn unsupported(input: &DeriveInput) -> syn::Error {
+    syn::Error::new_spanned(
+        &input.ident,
+        "`Variables` can only be derived for structs with named fields",
+    )
+}
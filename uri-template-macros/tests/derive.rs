@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use uri_template::{UriTemplate, Value, Variables};
+use uri_template_macros::Variables;
+
+#[derive(Variables)]
+struct Search {
+    owner: String,
+    repo: &'static str,
+    page: u32,
+    #[uri(rename = "page.size")]
+    page_size: Option<u32>,
+    labels: Vec<String>,
+    filters: BTreeMap<String, String>,
+    #[uri(skip)]
+    #[allow(dead_code)]
+    token: String,
+}
+
+#[derive(Variables)]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[derive(Variables)]
+struct Empty;
+
+fn search() -> Search {
+    let mut filters = BTreeMap::new();
+    filters.insert("state".to_string(), "open".to_string());
+    Search {
+        owner: "rust-lang".to_string(),
+        repo: "rust",
+        page: 2,
+        page_size: None,
+        labels: vec!["bug".to_string(), "help wanted".to_string()],
+        filters,
+        token: "secret".to_string(),
+    }
+}
+
+#[test]
+fn test_get() {
+    let search = search();
+    assert_eq!(search.get("owner"), Some(Value::from_string("rust-lang")));
+    assert_eq!(search.get("page"), Some(Value::Integer(2)));
+    assert_eq!(search.get("page.size"), None);
+    assert_eq!(search.get("page_size"), None);
+    assert_eq!(
+        search.get("labels"),
+        Some(Value::from_list(vec!["bug", "help wanted"]))
+    );
+    assert_eq!(
+        search.get("filters"),
+        Some(Value::from_assoc(vec![("state", "open")]))
+    );
+    assert_eq!(search.get("token"), None);
+}
+
+#[test]
+fn test_expand() {
+    let uri_template =
+        UriTemplate::parse("/repos/{owner}/{repo}/issues{?page,page.size,labels,filters*}");
+    let left = uri_template.expand(&search());
+    assert_eq!(
+        left,
+        "/repos/rust-lang/rust/issues?page=2&labels=bug,help%20wanted&state=open"
+    );
+
+    let search = Search {
+        page_size: Some(50),
+        ..search()
+    };
+    let left = uri_template.expand(&search);
+    assert_eq!(
+        left,
+        "/repos/rust-lang/rust/issues?page=2&page.size=50&labels=bug,help%20wanted&state=open"
+    );
+}
+
+#[test]
+fn test_generics() {
+    let uri_template = UriTemplate::parse("{value}{empty}");
+    assert_eq!(uri_template.expand(&Wrapper { value: 1.5 }), "1.5");
+    assert_eq!(uri_template.expand(&Empty), "");
+}
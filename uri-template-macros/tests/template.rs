@@ -0,0 +1,22 @@
+use uri_template::{UriTemplate, Value};
+use uri_template_macros::uri_template;
+
+#[test]
+fn test_uri_template() {
+    let left = uri_template!("/repos/{owner}/{repo}{?page,q:3}{#section*}");
+    let right = UriTemplate::try_parse("/repos/{owner}/{repo}{?page,q:3}{#section*}").unwrap();
+    assert_eq!(left, right);
+
+    let variables = vec![
+        ("owner".to_string(), Value::from_string("rust-lang")),
+        ("repo".to_string(), Value::from_string("rust")),
+        ("page".to_string(), Value::from_string("2")),
+    ];
+    assert_eq!(left.expand(&variables), "/repos/rust-lang/rust?page=2");
+}
+
+#[test]
+fn test_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,42 @@
+use uri_template::{Value, Variables};
+use uri_template_macros::typed_uri_template;
+
+#[typed_uri_template("/users/{id}{?fields*}")]
+struct UserUri;
+
+#[typed_uri_template("/repos/{owner}/{repo}/issues{/path*}{?q:3,labels*,page.size}{#type}")]
+#[derive(Clone, Debug)]
+pub struct IssuesUri;
+
+#[test]
+fn test_user_uri() {
+    assert_eq!(UserUri::new(42).to_string(), "/users/42");
+    assert_eq!(
+        UserUri::new(42).fields(vec!["name", "email"]).to_string(),
+        "/users/42?fields=name&fields=email"
+    );
+    assert_eq!(
+        UserUri::new("me")
+            .fields_assoc(vec![("a", 1), ("b", 2)])
+            .to_string(),
+        "/users/me?a=1&b=2"
+    );
+    assert_eq!(UserUri::uri_template().to_string(), "/users/{id}{?fields*}");
+}
+
+#[test]
+fn test_issues_uri() {
+    let uri = IssuesUri::new("rust-lang", "rust", vec!["open", "mine"])
+        .q("query")
+        .page_size(50)
+        .r#type("top");
+    assert_eq!(
+        uri.to_string(),
+        "/repos/rust-lang/rust/issues/open/mine?q=que&page.size=50#top"
+    );
+    assert_eq!(uri.get("page.size"), Some(&Value::from_string("50")));
+    assert_eq!(
+        uri.clone().labels(vec!["bug"]).get("labels"),
+        Some(&Value::from_list(vec!["bug"]))
+    );
+}
@@ -0,0 +1,6 @@
+use uri_template_macros::typed_uri_template;
+
+#[typed_uri_template("/users{?tags*,tags_assoc}")]
+struct Uri;
+
+fn main() {}
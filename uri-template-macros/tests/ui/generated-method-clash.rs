@@ -0,0 +1,6 @@
+use uri_template_macros::typed_uri_template;
+
+#[typed_uri_template("/users{?new}")]
+struct Uri;
+
+fn main() {}
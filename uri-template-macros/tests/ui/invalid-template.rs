@@ -0,0 +1,5 @@
+use uri_template_macros::uri_template;
+
+fn main() {
+    let _ = uri_template!("/repos/{owner}/{!repo}{?page*3}");
+}
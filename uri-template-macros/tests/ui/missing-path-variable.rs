@@ -0,0 +1,8 @@
+use uri_template_macros::typed_uri_template;
+
+#[typed_uri_template("/repos/{owner}/{repo}{?page}")]
+struct RepoUri;
+
+fn main() {
+    let _ = RepoUri::new("rust-lang").page(2);
+}
@@ -0,0 +1,6 @@
+use uri_template_macros::typed_uri_template;
+
+#[typed_uri_template("/{a.b}/{a_b}")]
+struct Uri;
+
+fn main() {}